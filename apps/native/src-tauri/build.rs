@@ -1,3 +1,76 @@
-fn main() {
-    tauri_build::build()
-}
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Frontend build embedded by the web server for browser mode
+const DIST_DIR: &str = "../dist";
+
+/// Smaller files are sent as is; matches `MIN_COMPRESS_SIZE` in src/assets.rs
+const MIN_COMPRESS_SIZE: u64 = 1024;
+
+/// Extensions of the files the web server sends compressed
+const COMPRESSIBLE: &[&str] = &["html", "js", "mjs", "css", "json", "svg", "wasm", "txt"];
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", DIST_DIR);
+    // Debug builds serve the frontend from the Vite dev server instead
+    if std::env::var("PROFILE").as_deref() == Ok("release") {
+        if let Err(e) = precompress(Path::new(DIST_DIR)) {
+            println!("cargo:warning=Failed to precompress {}: {}", DIST_DIR, e);
+        }
+    }
+    tauri_build::build()
+}
+
+/// Write a `.br` and a `.gz` next to every compressible file in `dir`, so the
+/// web server never has to compress them at runtime. Files already
+/// compressed since they last changed are skipped.
+fn precompress(dir: &Path) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            precompress(&path)?;
+            continue;
+        }
+        let compressible = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| COMPRESSIBLE.contains(&ext));
+        let metadata = fs::metadata(&path)?;
+        if !compressible || metadata.len() < MIN_COMPRESS_SIZE {
+            continue;
+        }
+        let (br_path, gz_path) = (with_suffix(&path, "br"), with_suffix(&path, "gz"));
+        let modified = metadata.modified()?;
+        let fresh = |path: &Path| {
+            fs::metadata(path)
+                .and_then(|m| m.modified())
+                .is_ok_and(|compressed| compressed >= modified)
+        };
+        if fresh(&br_path) && fresh(&gz_path) {
+            continue;
+        }
+
+        let data = fs::read(&path)?;
+        let mut br = Vec::new();
+        let params = brotli::enc::BrotliEncoderParams {
+            quality: 11,
+            ..Default::default()
+        };
+        brotli::BrotliCompress(&mut &data[..], &mut br, &params)?;
+        fs::write(&br_path, br)?;
+
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+        gz.write_all(&data)?;
+        fs::write(&gz_path, gz.finish()?)?;
+    }
+    Ok(())
+}
+
+/// `path` with `.suffix` appended to the whole file name, e.g. `app.js.br`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
@@ -0,0 +1,136 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::session::{find_token, random_hex, unix_ms, SessionScope};
+
+/// Every personal access token starts with this, so it can be told apart
+/// from a browser session token and spotted by secret scanners
+pub const TOKEN_PREFIX: &str = "mip_";
+
+/// Key the token list is stored under in the auth store
+pub const ACCESS_TOKENS_KEY: &str = "access_tokens";
+
+/// Longest token name kept
+const MAX_NAME_LEN: usize = 100;
+
+/// Stored form of a personal access token. Only the SHA-256 hash of the
+/// token is kept; the token itself is shown once when it is created.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessToken {
+    id: String,
+    name: String,
+    scope: SessionScope,
+    token_hash: String,
+    created_at: u64,
+}
+
+/// Personal access token as reported to the desktop UI (timestamps in unix ms)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scope: SessionScope,
+    pub created_at: u64,
+}
+
+impl From<&AccessToken> for AccessTokenInfo {
+    fn from(token: &AccessToken) -> Self {
+        Self {
+            id: token.id.clone(),
+            name: token.name.clone(),
+            scope: token.scope,
+            created_at: token.created_at,
+        }
+    }
+}
+
+/// Long-lived, named tokens for scripts talking to the local API
+///
+/// Unlike browser sessions they never expire; they are sent as
+/// `Authorization: Bearer mip_...` and last until revoked.
+pub struct AccessTokens {
+    tokens: Mutex<Vec<AccessToken>>,
+}
+
+impl AccessTokens {
+    pub fn new(tokens: Vec<AccessToken>) -> Self {
+        Self {
+            tokens: Mutex::new(tokens),
+        }
+    }
+
+    /// Create a token limited to `scope`. Returns the token itself, which
+    /// cannot be recovered later, along with its description.
+    pub fn create(
+        &self,
+        name: &str,
+        scope: SessionScope,
+    ) -> Result<(String, AccessTokenInfo), String> {
+        let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+        if name.is_empty() {
+            return Err("Token name cannot be empty".to_string());
+        }
+
+        let token = format!("{}{}", TOKEN_PREFIX, random_hex(32));
+        let record = AccessToken {
+            id: random_hex(8),
+            name,
+            scope,
+            token_hash: hash(&token),
+            created_at: unix_ms(SystemTime::now()),
+        };
+        let info = AccessTokenInfo::from(&record);
+        self.tokens.lock().unwrap().push(record);
+        Ok((token, info))
+    }
+
+    /// Scope of the token, or `None` if it is not a live access token
+    pub fn validate(&self, token: &str) -> Option<SessionScope> {
+        if !token.starts_with(TOKEN_PREFIX) {
+            return None;
+        }
+        let candidate = hash(token);
+        let tokens = self.tokens.lock().unwrap();
+        let index = find_token(tokens.iter().map(|t| t.token_hash.as_str()), &candidate)?;
+        Some(tokens[index].scope)
+    }
+
+    /// Tokens, oldest first
+    pub fn list(&self) -> Vec<AccessTokenInfo> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .iter()
+            .map(AccessTokenInfo::from)
+            .collect()
+    }
+
+    /// Revoke one token by id. Returns `false` if no such token exists.
+    pub fn revoke(&self, token_id: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        let before = tokens.len();
+        tokens.retain(|t| t.id != token_id);
+        tokens.len() != before
+    }
+
+    /// Stored form of every token, for persisting
+    pub fn records(&self) -> Vec<AccessToken> {
+        self.tokens.lock().unwrap().clone()
+    }
+}
+
+impl Default for AccessTokens {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+pub type SharedAccessTokens = Arc<AccessTokens>;
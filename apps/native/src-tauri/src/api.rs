@@ -0,0 +1,207 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    routing::get,
+    Json, Router,
+};
+
+use crate::data_store::{DesktopChange, Stored};
+use crate::events::ServerEvent;
+use crate::models::{
+    Account, Budget, Category, Debt, DebtSettlement, Transaction, TransactionFilter,
+};
+use crate::session::SessionScope;
+use crate::web_server::{validate_token, ApiResponse, AppState};
+
+pub(crate) type ApiError = (StatusCode, Json<ApiResponse<()>>);
+
+fn api_error(status: StatusCode, message: impl Into<String>) -> ApiError {
+    (status, Json(ApiResponse::error(message)))
+}
+
+fn not_found<T: Stored>(id: &str) -> ApiError {
+    api_error(
+        StatusCode::NOT_FOUND,
+        format!("{} {} not found", T::NAME, id),
+    )
+}
+
+/// REST routes for the synced data tables
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route(
+            "/api/v1/transactions",
+            get(list_transactions).post(create_record::<Transaction>),
+        )
+        .route("/api/v1/transactions/:id", record_routes::<Transaction>())
+        .merge(table_routes::<Account>("/api/v1/accounts"))
+        .merge(table_routes::<Category>("/api/v1/categories"))
+        .merge(table_routes::<Budget>("/api/v1/budgets"))
+        .merge(table_routes::<Debt>("/api/v1/debts"))
+        .merge(table_routes::<DebtSettlement>("/api/v1/debt-settlements"))
+}
+
+/// List/create on `path` and get/update/delete on `path/:id`
+fn table_routes<T: Stored>(path: &str) -> Router<AppState> {
+    Router::new()
+        .route(path, get(list_records::<T>).post(create_record::<T>))
+        .route(&format!("{}/:id", path), record_routes::<T>())
+}
+
+fn record_routes<T: Stored>() -> axum::routing::MethodRouter<AppState> {
+    get(get_record::<T>)
+        .put(update_record::<T>)
+        .delete(delete_record::<T>)
+}
+
+async fn list_transactions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(filter): Query<TransactionFilter>,
+) -> Result<Json<ApiResponse<Vec<Transaction>>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+    Ok(Json(ApiResponse::success(transactions(&state, &filter))))
+}
+
+async fn list_records<T: Stored>(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<T>>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+    Ok(Json(ApiResponse::success(
+        state.data_store.list(|_: &T| true),
+    )))
+}
+
+async fn get_record<T: Stored>(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<T>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+    Ok(Json(ApiResponse::success(find::<T>(&state, &id)?)))
+}
+
+async fn create_record<T: Stored>(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(input): Json<T::Input>,
+) -> Result<(StatusCode, Json<ApiResponse<T>>), ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadWrite)?;
+    let record = create::<T>(&state, input)?;
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(record))))
+}
+
+async fn update_record<T: Stored>(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(input): Json<T::Input>,
+) -> Result<Json<ApiResponse<T>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadWrite)?;
+    Ok(Json(ApiResponse::success(update::<T>(&state, &id, input)?)))
+}
+
+async fn delete_record<T: Stored>(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadWrite)?;
+    delete::<T>(&state, &id)?;
+    Ok(Json(ApiResponse::success(())))
+}
+
+// Data operations shared by the REST handlers and the WebSocket RPC
+
+/// Transactions matching `filter`, newest first
+pub(crate) fn transactions(state: &AppState, filter: &TransactionFilter) -> Vec<Transaction> {
+    let mut transactions = state.data_store.list(|t: &Transaction| t.matches(filter));
+    transactions.sort_by(|a, b| {
+        b.date
+            .cmp(&a.date)
+            .then_with(|| b.created_at.cmp(&a.created_at))
+    });
+    transactions
+}
+
+pub(crate) fn find<T: Stored>(state: &AppState, id: &str) -> Result<T, ApiError> {
+    state
+        .data_store
+        .get::<T>(id)
+        .ok_or_else(|| not_found::<T>(id))
+}
+
+pub(crate) fn create<T: Stored>(state: &AppState, input: T::Input) -> Result<T, ApiError> {
+    let record = T::create(uuid::Uuid::new_v4().to_string(), input)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?;
+    require_debt(state, &record)?;
+    let record = state
+        .data_store
+        .insert(record)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    state.bridge.data_written(&DesktopChange::upsert(&record));
+    state.events.publish(ServerEvent::created(&record));
+    publish_settled_debts(state, [record.settles_debt(), None]);
+    Ok(record)
+}
+
+pub(crate) fn update<T: Stored>(
+    state: &AppState,
+    id: &str,
+    input: T::Input,
+) -> Result<T, ApiError> {
+    let existing = find::<T>(state, id)?;
+    let record = existing
+        .update(input)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e))?;
+    require_debt(state, &record)?;
+    let replaced = state
+        .data_store
+        .replace(record.clone())
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !replaced {
+        return Err(not_found::<T>(id));
+    }
+    state.bridge.data_written(&DesktopChange::upsert(&record));
+    state.events.publish(ServerEvent::updated(&record));
+    publish_settled_debts(state, [existing.settles_debt(), record.settles_debt()]);
+    Ok(record)
+}
+
+pub(crate) fn delete<T: Stored>(state: &AppState, id: &str) -> Result<(), ApiError> {
+    let existing = find::<T>(state, id)?;
+    let deleted = state
+        .data_store
+        .delete::<T>(id)
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e))?;
+    if !deleted {
+        return Err(not_found::<T>(id));
+    }
+    state.bridge.data_written(&DesktopChange::delete::<T>(id));
+    state.events.publish(ServerEvent::deleted::<T>(id));
+    publish_settled_debts(state, [existing.settles_debt(), None]);
+    Ok(())
+}
+
+/// A settlement has to count towards an existing debt
+fn require_debt<T: Stored>(state: &AppState, record: &T) -> Result<(), ApiError> {
+    match record.settles_debt() {
+        Some(debt_id) if state.data_store.get::<Debt>(debt_id).is_none() => Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("Debt {} not found", debt_id),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Report debts the store recomputed after a settlement was written
+fn publish_settled_debts(state: &AppState, debt_ids: [Option<&str>; 2]) {
+    let [first, second] = debt_ids;
+    let second = second.filter(|id| Some(*id) != first);
+    let debts = [first, second].into_iter().flatten();
+    for debt in debts.filter_map(|id| state.data_store.get::<Debt>(id)) {
+        state.bridge.data_written(&DesktopChange::upsert(&debt));
+        state.events.publish(ServerEvent::updated(&debt));
+    }
+}
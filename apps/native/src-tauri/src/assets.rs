@@ -0,0 +1,233 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, HeaderMap, HeaderValue, Response, StatusCode, Uri},
+};
+#[cfg(any(not(debug_assertions), test))]
+use rust_embed::RustEmbed;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+/// Embed the dist folder at compile time (only in release mode)
+/// In debug mode, assets are served by Vite dev server, so we provide a dummy implementation
+#[cfg(not(debug_assertions))]
+#[derive(RustEmbed)]
+#[folder = "../dist"]
+struct Asset;
+
+/// A small stand-in for the dist folder, so tests can serve real files
+#[cfg(all(debug_assertions, test))]
+#[derive(RustEmbed)]
+#[folder = "tests/fixtures/dist"]
+struct Asset;
+
+/// Dummy Asset struct for debug/dev mode - returns None for all assets
+/// since the Vite dev server handles asset serving on port 1420
+#[cfg(all(debug_assertions, not(test)))]
+struct Asset;
+
+#[cfg(all(debug_assertions, not(test)))]
+impl Asset {
+    fn get(_path: &str) -> Option<rust_embed::EmbeddedFile> {
+        None // In dev mode, assets are served by Vite
+    }
+}
+
+/// Vite puts content-hashed bundles here, so they never change under the same name
+const HASHED_ASSETS_DIR: &str = "assets/";
+
+const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Smaller files are not worth compressing; build.rs uses the same limit
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+/// Gzip output per asset path, compressed on first request
+static GZIP_CACHE: OnceLock<Mutex<HashMap<String, Bytes>>> = OnceLock::new();
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Encoding {
+    fn name(self) -> Option<&'static str> {
+        match self {
+            Self::Identity => None,
+            Self::Gzip => Some("gzip"),
+            Self::Brotli => Some("br"),
+        }
+    }
+}
+
+/// Serve a file from the embedded dist folder
+///
+/// Unknown paths without a file extension are client-side routes and get
+/// `index.html`; unknown files get a 404 so broken bundle references surface.
+pub async fn serve_asset(uri: Uri, headers: HeaderMap) -> Response<Body> {
+    let path = uri.path().trim_start_matches('/');
+    let path = if path.is_empty() { "index.html" } else { path };
+
+    let (path, content) = match Asset::get(path) {
+        Some(content) => (path, content),
+        None if is_route(path) => match Asset::get("index.html") {
+            Some(content) => ("index.html", content),
+            None => return status(StatusCode::NOT_FOUND),
+        },
+        None => return status(StatusCode::NOT_FOUND),
+    };
+
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+    let (encoding, body) = match encode(&headers, path, &content.data, mime.essence_str()) {
+        Some((encoding, compressed)) => (encoding, Body::from(compressed)),
+        None => (Encoding::Identity, Body::from(content.data)),
+    };
+    let etag = etag(&content.metadata.sha256_hash(), encoding);
+    let cache_control = if path.starts_with(HASHED_ASSETS_DIR) {
+        IMMUTABLE
+    } else {
+        // Revalidate with the ETag so new builds show up immediately
+        "no-cache"
+    };
+
+    let mut builder = Response::builder()
+        .header(header::CONTENT_TYPE, mime.as_ref())
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ETAG, &etag)
+        .header(header::VARY, "Accept-Encoding");
+
+    if if_none_match(&headers, &etag) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Body::empty())
+            .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    if let Some(name) = encoding.name() {
+        builder = builder.header(header::CONTENT_ENCODING, name);
+    }
+    builder
+        .status(StatusCode::OK)
+        .body(body)
+        .unwrap_or_else(|_| status(StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+/// Paths whose last segment has no extension are SPA routes, not files
+fn is_route(path: &str) -> bool {
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    !file_name.contains('.') || file_name.ends_with(".html")
+}
+
+/// The file at `path` in the best encoding the client accepts, or `None` to
+/// send it as is. Release builds embed `.br` and `.gz` files written by
+/// build.rs; brotli is only sent when one exists, while gzip falls back to
+/// compressing at runtime.
+fn encode(headers: &HeaderMap, path: &str, data: &[u8], mime: &str) -> Option<(Encoding, Bytes)> {
+    if !compressible(data.len(), mime) {
+        return None;
+    }
+    let accepted = accepted_encodings(headers);
+    let accepts = |name: &str| accepted.iter().any(|a| a.eq_ignore_ascii_case(name));
+
+    if accepts("br") {
+        if let Some(file) = Asset::get(&format!("{}.br", path)) {
+            return Some((Encoding::Brotli, bytes(file.data)));
+        }
+    }
+    if accepts("gzip") {
+        // A file that fails to compress is sent as is
+        let compressed = match Asset::get(&format!("{}.gz", path)) {
+            Some(file) => bytes(file.data),
+            None => gzip(path, data)?,
+        };
+        return Some((Encoding::Gzip, compressed));
+    }
+    None
+}
+
+/// Encodings listed in `Accept-Encoding`, without the ones refused with `q=0`
+fn accepted_encodings(headers: &HeaderMap) -> Vec<&str> {
+    headers
+        .get_all(header::ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let name = parts.next()?;
+            let refused = parts
+                .any(|p| p.strip_prefix("q=").and_then(|q| q.parse::<f32>().ok()) == Some(0.0));
+            (!refused).then_some(name)
+        })
+        .collect()
+}
+
+fn compressible(size: usize, mime: &str) -> bool {
+    size >= MIN_COMPRESS_SIZE
+        && (mime.starts_with("text/")
+            || matches!(
+                mime,
+                "application/javascript"
+                    | "application/json"
+                    | "application/wasm"
+                    | "image/svg+xml"
+            ))
+}
+
+fn bytes(data: Cow<'static, [u8]>) -> Bytes {
+    match data {
+        Cow::Borrowed(data) => Bytes::from_static(data),
+        Cow::Owned(data) => Bytes::from(data),
+    }
+}
+
+/// Gzip of `data`, or `None` if compression failed
+fn gzip(path: &str, data: &[u8]) -> Option<Bytes> {
+    let cache = GZIP_CACHE.get_or_init(Default::default);
+    if let Some(compressed) = cache.lock().unwrap().get(path) {
+        return Some(compressed.clone());
+    }
+
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::best());
+    let compressed = match encoder.write_all(data).and_then(|_| encoder.finish()) {
+        Ok(compressed) => Bytes::from(compressed),
+        Err(e) => {
+            eprintln!("Failed to compress {}: {}", path, e);
+            return None;
+        }
+    };
+    cache
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), compressed.clone());
+    Some(compressed)
+}
+
+/// Strong ETag from the embedded file hash; each encoding is a different representation
+fn etag(hash: &[u8; 32], encoding: Encoding) -> String {
+    match encoding.name() {
+        Some(name) => format!("\"{}-{}\"", hex::encode(&hash[..16]), name),
+        None => format!("\"{}\"", hex::encode(&hash[..16])),
+    }
+}
+
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+fn status(code: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::empty());
+    *response.status_mut() = code;
+    response
+        .headers_mut()
+        .insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    response
+}
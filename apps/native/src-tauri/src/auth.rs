@@ -1,24 +1,29 @@
 use glean_oak_sync_client::{ReqwestHttpClient, GleanOakClient, SyncClientConfig};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tauri_plugin_store::StoreExt;
-use chacha20poly1305::{
-    aead::{Aead, KeyInit},
-    ChaCha20Poly1305
-};
-use argon2::{Argon2, PasswordHasher};
-use argon2::password_hash::SaltString;
-use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use jsonwebtoken::{decode, decode_header, DecodingKey, Validation};
 use tokio::sync::RwLock;
 
+use crate::secret_store::SharedSecretStore;
+use crate::single_flight::SingleFlight;
+
 pub struct AuthService {
     sync_client: Arc<RwLock<GleanOakClient<ReqwestHttpClient>>>,
     server_url: String,
     default_app_id: String,
     default_api_key: String,
+    /// Credentials and account details of every profile
+    secrets: SharedSecretStore,
+    /// Refresh in flight per profile, shared by all clones so concurrent
+    /// callers join one refresh
+    refresh_flights: Arc<Mutex<HashMap<String, Arc<RefreshFlight>>>>,
 }
 
+type RefreshFlight = SingleFlight<Result<(), RefreshError>>;
+
 impl Clone for AuthService {
     fn clone(&self) -> Self {
         Self {
@@ -26,6 +31,8 @@ impl Clone for AuthService {
             server_url: self.server_url.clone(),
             default_app_id: self.default_app_id.clone(),
             default_api_key: self.default_api_key.clone(),
+            secrets: Arc::clone(&self.secrets),
+            refresh_flights: Arc::clone(&self.refresh_flights),
         }
     }
 }
@@ -52,15 +59,98 @@ pub struct AuthStatus {
     pub server_url: Option<String>,
 }
 
-const STORE_FILE: &str = "auth.json";
+/// A signed-in account on this device, as listed in the profile switcher
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub name: String,
+    pub user_id: Option<String>,
+    pub email: Option<String>,
+    pub is_authenticated: bool,
+    pub is_active: bool,
+}
+
+/// Why a token refresh failed
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefreshError {
+    /// The sync server could not be reached or is struggling; worth retrying
+    Unavailable(String),
+    /// The sync server turned the refresh token down, or there is none to present
+    Rejected(String),
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unavailable(e) | Self::Rejected(e) => write!(f, "Token refresh failed: {}", e),
+        }
+    }
+}
+
+impl From<RefreshError> for String {
+    fn from(e: RefreshError) -> Self {
+        e.to_string()
+    }
+}
+
+/// Statuses and reasons in a sync client error that mean the refresh token was turned down
+const REJECTION_MARKERS: &[&str] = &["400", "401", "403", "unauthorized", "forbidden", "expired", "revoked"];
+
+/// Classify a failed refresh by the sync client's error. The client only
+/// reports failures as text, so rejections are recognised by the status or
+/// reason in it; anything else is taken for a network or server problem.
+fn classify_refresh_error(error: String) -> RefreshError {
+    let lowercase = error.to_lowercase();
+    let rejected = lowercase
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .any(|word| REJECTION_MARKERS.contains(&word));
+    if rejected {
+        RefreshError::Rejected(error)
+    } else {
+        RefreshError::Unavailable(error)
+    }
+}
+
+const REFRESH_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub const STORE_FILE: &str = "auth.json";
 const KEY_ACCESS_TOKEN: &str = "access_token";
 const KEY_REFRESH_TOKEN: &str = "refresh_token";
 const KEY_USER_ID: &str = "user_id";
+const KEY_EMAIL: &str = "email";
 const KEY_APPS: &str = "apps";
 const KEY_IS_ADMIN: &str = "is_admin";
 const KEY_SERVER_URL: &str = "server_url";
 const KEY_APP_ID: &str = "app_id";
 const KEY_API_KEY: &str = "api_key";
+const KEY_PROFILES: &str = "profiles";
+const KEY_ACTIVE_PROFILE: &str = "active_profile";
+/// Profile the local data is synced with
+const KEY_DATA_PROFILE: &str = "data_profile";
+
+/// Profile used until another one is created
+pub const DEFAULT_PROFILE: &str = "default";
+const MAX_PROFILE_NAME_LEN: usize = 50;
+
+/// Store key of `key` in `profile`'s credential namespace. The default profile
+/// keeps the bare keys, so stores written before profiles existed still load.
+fn profile_key(profile: &str, key: &str) -> String {
+    if profile == DEFAULT_PROFILE {
+        key.to_string()
+    } else {
+        format!("profiles/{}/{}", profile, key)
+    }
+}
+
+fn validate_profile_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+        return Err(format!("Profile name must be 1 to {} characters", MAX_PROFILE_NAME_LEN));
+    }
+    if !name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.')) {
+        return Err("Profile name may only contain letters, digits, spaces, '-', '_' and '.'".to_string());
+    }
+    Ok(())
+}
 
 #[derive(Debug, serde::Deserialize)]
 struct TokenClaims {
@@ -71,7 +161,8 @@ struct TokenClaims {
     sub: Option<String>,
 }
 
-fn is_token_expired(token: &str) -> Result<bool, String> {
+/// Expiry of a JWT as unix seconds, read without verifying the signature
+pub fn token_expires_at(token: &str) -> Result<i64, String> {
     let header = decode_header(token)
         .map_err(|e| format!("Failed to decode token header: {}", e))?;
     let mut validation = Validation::new(header.alg);
@@ -82,96 +173,22 @@ fn is_token_expired(token: &str) -> Result<bool, String> {
         &DecodingKey::from_secret(&[]),
         &validation,
     ).map_err(|e| format!("Failed to decode token: {}", e))?;
-    let now = std::time::SystemTime::now()
+    Ok(token_data.claims.exp)
+}
+
+pub fn unix_now() -> Result<i64, String> {
+    Ok(std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map_err(|e| format!("Failed to get current time: {}", e))?
-        .as_secs() as i64;
-    Ok(token_data.claims.exp < now)
+        .as_secs() as i64)
 }
 
-mod crypto {
-    use super::*;
-
-    fn get_device_identifier() -> Result<String, String> {
-        #[cfg(target_os = "android")]
-        {
-            let android_id = std::env::var("ANDROID_DATA")
-                .or_else(|_| std::env::var("EXTERNAL_STORAGE"))
-                .unwrap_or_else(|_| "money-insight-android-device".to_string());
-            use sha2::{Sha256, Digest};
-            let mut hasher = Sha256::new();
-            hasher.update(android_id.as_bytes());
-            hasher.update(b"money-insight-unique-salt");
-            let result = hasher.finalize();
-            Ok(hex::encode(result))
-        }
-
-        #[cfg(not(target_os = "android"))]
-        {
-            machine_uid::get()
-                .map_err(|e| format!("Failed to get machine ID: {}", e))
-        }
-    }
-
-    fn derive_encryption_key() -> Result<[u8; 32], String> {
-        let machine_id = get_device_identifier()?;
-        let app_salt = b"money-insight-auth-v1";
-        let combined = format!("{}{}", machine_id, String::from_utf8_lossy(app_salt));
-        let salt = SaltString::from_b64("bW9uZXlpbnNpZ2h0c2FsdDEyMzQ1")
-            .map_err(|e| format!("Failed to create salt: {}", e))?;
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password(combined.as_bytes(), &salt)
-            .map_err(|e| format!("Failed to hash password: {}", e))?;
-        let hash_str = password_hash.hash.ok_or("No hash generated")?;
-        let hash_bytes = hash_str.as_bytes();
-        if hash_bytes.len() < 32 {
-            return Err("Hash too short".to_string());
-        }
-        let mut key = [0u8; 32];
-        key.copy_from_slice(&hash_bytes[..32]);
-        Ok(key)
-    }
-
-    pub fn encrypt(plaintext: &str) -> Result<String, String> {
-        let key_bytes = derive_encryption_key()?;
-        let cipher = ChaCha20Poly1305::new_from_slice(&key_bytes)
-            .map_err(|e| format!("Failed to create cipher: {}", e))?;
-        let nonce_bytes: [u8; 12] = rand::random();
-        let nonce = chacha20poly1305::Nonce::from(nonce_bytes);
-        let ciphertext = cipher
-            .encrypt(&nonce, plaintext.as_bytes())
-            .map_err(|e| format!("Encryption failed: {}", e))?;
-        let mut result = Vec::with_capacity(nonce_bytes.len() + ciphertext.len());
-        result.extend_from_slice(&nonce_bytes);
-        result.extend_from_slice(&ciphertext);
-        Ok(BASE64.encode(&result))
-    }
-
-    pub fn decrypt(encrypted: &str) -> Result<String, String> {
-        let key_bytes = derive_encryption_key()?;
-        let cipher = ChaCha20Poly1305::new_from_slice(&key_bytes)
-            .map_err(|e| format!("Failed to create cipher: {}", e))?;
-        let encrypted_bytes = BASE64
-            .decode(encrypted)
-            .map_err(|e| format!("Failed to decode base64: {}", e))?;
-        if encrypted_bytes.len() < 12 {
-            return Err("Encrypted data too short".to_string());
-        }
-        let (nonce_bytes, ciphertext) = encrypted_bytes.split_at(12);
-        let mut nonce_array = [0u8; 12];
-        nonce_array.copy_from_slice(nonce_bytes);
-        let nonce = chacha20poly1305::Nonce::from(nonce_array);
-        let plaintext_bytes = cipher
-            .decrypt(&nonce, ciphertext)
-            .map_err(|e| format!("Decryption failed: {}", e))?;
-        String::from_utf8(plaintext_bytes)
-            .map_err(|e| format!("Failed to convert decrypted data to string: {}", e))
-    }
+fn is_token_expired(token: &str) -> Result<bool, String> {
+    Ok(token_expires_at(token)? < unix_now()?)
 }
 
 impl AuthService {
-    pub fn new(server_url: String, default_app_id: String, default_api_key: String) -> Self {
+    pub fn new(server_url: String, default_app_id: String, default_api_key: String, secrets: SharedSecretStore) -> Self {
         let config = SyncClientConfig::new(&server_url, &default_app_id, &default_api_key);
         let http = ReqwestHttpClient::new();
         let sync_client = GleanOakClient::new(config, http);
@@ -180,6 +197,8 @@ impl AuthService {
             server_url,
             default_app_id,
             default_api_key,
+            secrets,
+            refresh_flights: Arc::default(),
         }
     }
 
@@ -193,7 +212,6 @@ impl AuthService {
 
     pub async fn register(
         &self,
-        app_handle: &tauri::AppHandle,
         username: String,
         email: String,
         password: String,
@@ -210,13 +228,12 @@ impl AuthService {
             apps: if result.apps.is_empty() { None } else { Some(result.apps) },
             is_admin: if result.is_admin { Some(true) } else { None },
         };
-        self.store_auth_data(app_handle, &auth_response, &app_id, &api_key).await?;
+        self.store_auth_data(&auth_response, &email, &app_id, &api_key).await?;
         Ok(auth_response)
     }
 
     pub async fn login(
         &self,
-        app_handle: &tauri::AppHandle,
         email: String,
         password: String,
     ) -> Result<AuthResponse, String> {
@@ -232,124 +249,202 @@ impl AuthService {
             apps: if result.apps.is_empty() { None } else { Some(result.apps) },
             is_admin: if result.is_admin { Some(true) } else { None },
         };
-        self.store_auth_data(app_handle, &auth_response, &app_id, &api_key).await?;
+        self.store_auth_data(&auth_response, &email, &app_id, &api_key).await?;
         Ok(auth_response)
     }
 
-    pub async fn refresh_token(&self, app_handle: &tauri::AppHandle) -> Result<(), String> {
-        let refresh_token = self.get_refresh_token(app_handle).await?;
-        let access_token = self.get_access_token(app_handle).await.unwrap_or_default();
-        {
-            let client = self.sync_client.read().await;
-            client.set_tokens(access_token, refresh_token, None).await;
-        }
-        {
-            let client = self.sync_client.read().await;
-            client.refresh_token().await.map_err(|e| format!("Token refresh failed: {}", e))?;
+    /// Refresh the stored tokens, joining a refresh that is already in flight.
+    /// The server rotates refresh tokens, so a second concurrent refresh would
+    /// present a revoked one and sign the user out.
+    pub async fn refresh_token(&self) -> Result<(), RefreshError> {
+        // Pinned up front so a profile switch mid-refresh cannot mix up credentials
+        let profile = self.active_profile().map_err(RefreshError::Rejected)?;
+        let flight = Arc::clone(self.refresh_flights.lock().unwrap().entry(profile.clone()).or_default());
+        let auth = self.clone();
+        flight
+            .run(|| async move { auth.refresh_token_now(&profile).await })
+            .await
+    }
+
+    async fn refresh_token_now(&self, profile: &str) -> Result<(), RefreshError> {
+        let refresh_token = self.get_secret(profile, KEY_REFRESH_TOKEN, "No refresh token found")
+            .map_err(RefreshError::Rejected)?;
+        let access_token = self.get_secret(profile, KEY_ACCESS_TOKEN, "No access token found").unwrap_or_default();
+        let (server_url, app_id, api_key) = self.sync_target().map_err(RefreshError::Rejected)?;
+
+        // A client of its own, so a refresh for another profile cannot swap its tokens
+        let config = SyncClientConfig::new(&server_url, &app_id, &api_key);
+        let client = GleanOakClient::new(config, ReqwestHttpClient::new());
+        client.set_tokens(access_token, refresh_token, None).await;
+        match tokio::time::timeout(REFRESH_TIMEOUT, client.refresh_token()).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => return Err(classify_refresh_error(e.to_string())),
+            Err(_) => return Err(RefreshError::Unavailable("Timed out".to_string())),
         }
-        let (new_access, new_refresh) = {
-            let client = self.sync_client.read().await;
-            client.get_tokens().await
+        let (Some(access_token), Some(refresh_token)) = client.get_tokens().await else {
+            return Err(RefreshError::Rejected("No tokens after refresh".to_string()));
         };
-        self.update_tokens_raw(
-            app_handle,
-            &new_access.ok_or_else(|| "No access token after refresh".to_string())?,
-            &new_refresh.ok_or_else(|| "No refresh token after refresh".to_string())?,
-        ).await?;
-        Ok(())
+        self.update_tokens_raw(profile, &access_token, &refresh_token).await
+            .map_err(RefreshError::Unavailable)
     }
 
-    pub async fn logout(&self, app_handle: &tauri::AppHandle) -> Result<(), String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        store.delete(KEY_ACCESS_TOKEN);
-        store.delete(KEY_REFRESH_TOKEN);
-        store.delete(KEY_USER_ID);
-        store.delete(KEY_APPS);
-        store.delete(KEY_IS_ADMIN);
-        store.delete(KEY_APP_ID);
-        store.delete(KEY_API_KEY);
-        store.save().map_err(|e| format!("Failed to save store: {}", e))?;
-        Ok(())
+    /// Sign the active profile out. Other profiles and the sync configuration are kept.
+    pub async fn logout(&self) -> Result<(), String> {
+        let profile = self.active_profile()?;
+        for key in [KEY_ACCESS_TOKEN, KEY_REFRESH_TOKEN, KEY_USER_ID, KEY_EMAIL, KEY_APPS, KEY_IS_ADMIN] {
+            self.secrets.delete(&profile_key(&profile, key))?;
+        }
+        self.secrets.save()
     }
 
-    pub async fn get_access_token(&self, app_handle: &tauri::AppHandle) -> Result<String, String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted = store.get(KEY_ACCESS_TOKEN)
-            .and_then(|v| v.as_str().map(|s| s.to_string()))
-            .ok_or_else(|| "No access token found".to_string())?;
-        crypto::decrypt(&encrypted)
+    pub async fn get_access_token(&self) -> Result<String, String> {
+        let profile = self.active_profile()?;
+        self.get_secret(&profile, KEY_ACCESS_TOKEN, "No access token found")
     }
 
-    pub async fn get_refresh_token(&self, app_handle: &tauri::AppHandle) -> Result<String, String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted = store.get(KEY_REFRESH_TOKEN)
+    pub async fn get_refresh_token(&self) -> Result<String, String> {
+        let profile = self.active_profile()?;
+        self.get_secret(&profile, KEY_REFRESH_TOKEN, "No refresh token found")
+    }
+
+    fn get_secret(&self, profile: &str, key: &str, missing: &str) -> Result<String, String> {
+        self.secrets.get_secret(&profile_key(profile, key))?
+            .ok_or_else(|| missing.to_string())
+    }
+
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.secrets.get(key).ok().flatten()
             .and_then(|v| v.as_str().map(|s| s.to_string()))
-            .ok_or_else(|| "No refresh token found".to_string())?;
-        crypto::decrypt(&encrypted)
     }
 
-    pub fn get_stored_api_key(&self, app_handle: &tauri::AppHandle) -> Result<String, String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted = store.get(KEY_API_KEY)
+    /// Profile whose credentials are in use
+    pub fn active_profile(&self) -> Result<String, String> {
+        Ok(self.secrets.get(KEY_ACTIVE_PROFILE)?
             .and_then(|v| v.as_str().map(|s| s.to_string()))
-            .ok_or_else(|| "No API key found".to_string())?;
-        crypto::decrypt(&encrypted)
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_string()))
+    }
+
+    /// Every profile on this device, default first
+    pub fn list_profiles(&self) -> Result<Vec<ProfileInfo>, String> {
+        let active = self.active_profile()?;
+        Ok(self.profile_names()?.into_iter().map(|name| ProfileInfo {
+            user_id: self.get_string(&profile_key(&name, KEY_USER_ID)),
+            email: self.get_string(&profile_key(&name, KEY_EMAIL)),
+            is_authenticated: matches!(self.secrets.get_secret(&profile_key(&name, KEY_ACCESS_TOKEN)), Ok(Some(_))),
+            is_active: name == active,
+            name,
+        }).collect())
+    }
+
+    /// Make `name` the active profile, creating it signed out if it is new
+    pub fn switch_profile(&self, name: &str) -> Result<(), String> {
+        let name = name.trim();
+        validate_profile_name(name)?;
+        let mut names = self.profile_names()?;
+        if !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+            self.secrets.set(KEY_PROFILES, serde_json::json!(names[1..]))?;
+        }
+        self.secrets.set(KEY_ACTIVE_PROFILE, serde_json::json!(name))?;
+        self.secrets.save()
+    }
+
+    /// Fail unless the local data was last synced with the active profile. The
+    /// data is shared by all profiles, so after a switch it is only synced
+    /// once the user confirms it belongs to the new account.
+    pub fn ensure_sync_allowed(&self) -> Result<(), String> {
+        let active = self.active_profile()?;
+        // Stores written before profiles existed synced the default profile
+        let synced = self.get_string(KEY_DATA_PROFILE).unwrap_or_else(|| DEFAULT_PROFILE.to_string());
+        if synced != active {
+            return Err(format!(
+                "Local data is synced with profile '{}'; confirm syncing it with '{}' first",
+                synced, active,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sync the local data with the active profile from now on
+    pub fn confirm_sync_profile(&self) -> Result<(), String> {
+        let active = self.active_profile()?;
+        self.secrets.set(KEY_DATA_PROFILE, serde_json::json!(active))?;
+        self.secrets.save()
+    }
+
+    /// Email the active profile signed in with
+    pub fn profile_email(&self) -> Option<String> {
+        let profile = self.active_profile().ok()?;
+        self.get_string(&profile_key(&profile, KEY_EMAIL))
+    }
+
+    // The default profile always exists and is not stored in the list
+    fn profile_names(&self) -> Result<Vec<String>, String> {
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+        if let Some(stored) = self.secrets.get(KEY_PROFILES)?.and_then(|v| v.as_array().cloned()) {
+            names.extend(stored.iter().filter_map(|v| v.as_str().map(|s| s.to_string())));
+        }
+        Ok(names)
+    }
+
+    pub fn get_stored_api_key(&self) -> Result<String, String> {
+        self.secrets.get_secret(KEY_API_KEY)?
+            .ok_or_else(|| "No API key found".to_string())
     }
 
-    pub async fn is_authenticated(&self, app_handle: &tauri::AppHandle) -> bool {
-        let token = match self.get_access_token(app_handle).await {
+    /// Access token that has not expired yet, refreshing it first if needed
+    pub async fn get_valid_access_token(&self, force_refresh: bool) -> Result<String, String> {
+        let token = self.get_access_token().await?;
+        if !force_refresh && !is_token_expired(&token).unwrap_or(true) {
+            return Ok(token);
+        }
+        self.refresh_token().await?;
+        self.get_access_token().await
+    }
+
+    /// Server URL, app ID and API key sync requests should use
+    pub fn sync_target(&self) -> Result<(String, String, String), String> {
+        let server_url = self.get_string(KEY_SERVER_URL).unwrap_or_else(|| self.server_url.clone());
+        let app_id = self.get_string(KEY_APP_ID).unwrap_or_else(|| self.default_app_id.clone());
+        let api_key = self.get_stored_api_key().unwrap_or_else(|_| self.default_api_key.clone());
+        Ok((server_url, app_id, api_key))
+    }
+
+    pub async fn is_authenticated(&self) -> bool {
+        let token = match self.get_access_token().await {
             Ok(t) => t,
             Err(_) => return false,
         };
         match is_token_expired(&token) {
-            Ok(true) => self.refresh_token(app_handle).await.is_ok(),
+            Ok(true) => self.refresh_token().await.is_ok(),
             Ok(false) => true,
             Err(_) => false,
         }
     }
 
-    pub async fn get_auth_status(&self, app_handle: &tauri::AppHandle) -> AuthStatus {
-        let store = match app_handle.store(STORE_FILE) {
-            Ok(s) => s,
-            Err(_) => return AuthStatus {
-                is_authenticated: false, user_id: None, apps: None, is_admin: None,
-                server_url: Some(self.server_url.clone()),
-            },
+    pub async fn get_auth_status(&self) -> AuthStatus {
+        let signed_out = AuthStatus {
+            is_authenticated: false, user_id: None, apps: None, is_admin: None,
+            server_url: Some(self.server_url.clone()),
         };
 
-        let encrypted_token = store.get(KEY_ACCESS_TOKEN)
-            .and_then(|v| v.as_str().map(|s| s.to_string()));
-
-        if encrypted_token.is_none() {
-            return AuthStatus {
-                is_authenticated: false, user_id: None, apps: None, is_admin: None,
-                server_url: Some(self.server_url.clone()),
-            };
-        }
-
-        let access_token = match crypto::decrypt(&encrypted_token.unwrap()) {
+        let profile = self.active_profile().unwrap_or_else(|_| DEFAULT_PROFILE.to_string());
+        let access_token = match self.get_secret(&profile, KEY_ACCESS_TOKEN, "No access token found") {
             Ok(token) => token,
-            Err(_) => return AuthStatus {
-                is_authenticated: false, user_id: None, apps: None, is_admin: None,
-                server_url: Some(self.server_url.clone()),
-            },
+            Err(_) => return signed_out,
         };
 
         let is_expired = is_token_expired(&access_token).unwrap_or(true);
-        if is_expired {
-            if self.refresh_token(app_handle).await.is_err() {
-                return AuthStatus {
-                    is_authenticated: false, user_id: None, apps: None, is_admin: None,
-                    server_url: Some(self.server_url.clone()),
-                };
-            }
+        if is_expired && self.refresh_token().await.is_err() {
+            return signed_out;
         }
 
-        let user_id = store.get(KEY_USER_ID).and_then(|v| v.as_str().map(|s| s.to_string()));
-        let apps = store.get(KEY_APPS).and_then(|v| {
+        let user_id = self.get_string(&profile_key(&profile, KEY_USER_ID));
+        let stored = |key: &str| self.secrets.get(&profile_key(&profile, key)).ok().flatten();
+        let apps = stored(KEY_APPS).and_then(|v| {
             v.as_array().map(|arr| arr.iter().filter_map(|v| v.as_str().map(|s| s.to_string())).collect())
         });
-        let is_admin = store.get(KEY_IS_ADMIN).and_then(|v| v.as_bool());
+        let is_admin = stored(KEY_IS_ADMIN).and_then(|v| v.as_bool());
 
         AuthStatus {
             is_authenticated: true,
@@ -359,59 +454,294 @@ impl AuthService {
     }
 
     async fn store_auth_data(
-        &self, app_handle: &tauri::AppHandle, auth_response: &AuthResponse,
-        app_id: &str, api_key: &str,
+        &self, auth_response: &AuthResponse,
+        email: &str, app_id: &str, api_key: &str,
     ) -> Result<(), String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted_access_token = crypto::encrypt(&auth_response.access_token)?;
-        let encrypted_refresh_token = crypto::encrypt(&auth_response.refresh_token)?;
-        let encrypted_api_key = crypto::encrypt(api_key)?;
-        store.set(KEY_ACCESS_TOKEN, serde_json::json!(encrypted_access_token));
-        store.set(KEY_REFRESH_TOKEN, serde_json::json!(encrypted_refresh_token));
-        store.set(KEY_USER_ID, serde_json::json!(&auth_response.user_id));
-        store.set(KEY_SERVER_URL, serde_json::json!(&self.server_url));
-        store.set(KEY_APP_ID, serde_json::json!(app_id));
-        store.set(KEY_API_KEY, serde_json::json!(encrypted_api_key));
-        if let Some(apps) = &auth_response.apps {
-            store.set(KEY_APPS, serde_json::json!(apps));
+        let profile = self.active_profile()?;
+        self.secrets.set_secret(&profile_key(&profile, KEY_ACCESS_TOKEN), &auth_response.access_token)?;
+        self.secrets.set_secret(&profile_key(&profile, KEY_REFRESH_TOKEN), &auth_response.refresh_token)?;
+        self.secrets.set(&profile_key(&profile, KEY_USER_ID), serde_json::json!(&auth_response.user_id))?;
+        self.secrets.set(&profile_key(&profile, KEY_EMAIL), serde_json::json!(email))?;
+        self.secrets.set(KEY_SERVER_URL, serde_json::json!(&self.server_url))?;
+        self.secrets.set(KEY_APP_ID, serde_json::json!(app_id))?;
+        self.secrets.set_secret(KEY_API_KEY, api_key)?;
+        match &auth_response.apps {
+            Some(apps) => self.secrets.set(&profile_key(&profile, KEY_APPS), serde_json::json!(apps))?,
+            None => self.secrets.delete(&profile_key(&profile, KEY_APPS))?,
         }
-        if let Some(is_admin) = auth_response.is_admin {
-            store.set(KEY_IS_ADMIN, serde_json::json!(is_admin));
+        match auth_response.is_admin {
+            Some(is_admin) => self.secrets.set(&profile_key(&profile, KEY_IS_ADMIN), serde_json::json!(is_admin))?,
+            None => self.secrets.delete(&profile_key(&profile, KEY_IS_ADMIN))?,
         }
-        store.save().map_err(|e| format!("Failed to save store: {}", e))?;
-        Ok(())
+        self.secrets.save()
     }
 
     async fn update_tokens_raw(
-        &self, app_handle: &tauri::AppHandle, access_token: &str, refresh_token: &str,
+        &self, profile: &str, access_token: &str, refresh_token: &str,
     ) -> Result<(), String> {
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted_access_token = crypto::encrypt(access_token)?;
-        let encrypted_refresh_token = crypto::encrypt(refresh_token)?;
-        store.set(KEY_ACCESS_TOKEN, serde_json::json!(encrypted_access_token));
-        store.set(KEY_REFRESH_TOKEN, serde_json::json!(encrypted_refresh_token));
-        store.save().map_err(|e| format!("Failed to save store: {}", e))?;
-        Ok(())
+        self.secrets.set_secret(&profile_key(profile, KEY_ACCESS_TOKEN), access_token)?;
+        self.secrets.set_secret(&profile_key(profile, KEY_REFRESH_TOKEN), refresh_token)?;
+        self.secrets.save()
     }
 
     pub async fn configure_sync(
-        &self, app_handle: &tauri::AppHandle,
+        &self,
         server_url: Option<String>, app_id: Option<String>, api_key: Option<String>,
     ) -> Result<(), String> {
         let new_server_url = server_url.unwrap_or_else(|| self.server_url.clone());
         let app_id = app_id.unwrap_or_else(|| self.default_app_id.clone());
         let api_key = api_key.unwrap_or_else(|| self.default_api_key.clone());
         self.set_server_url(new_server_url.clone()).await;
-        let store = app_handle.store(STORE_FILE).map_err(|e| format!("Failed to access store: {}", e))?;
-        let encrypted_api_key = crypto::encrypt(&api_key)?;
-        store.set(KEY_SERVER_URL, serde_json::json!(new_server_url));
-        store.set(KEY_APP_ID, serde_json::json!(app_id));
-        store.set(KEY_API_KEY, serde_json::json!(encrypted_api_key));
-        store.save().map_err(|e| format!("Failed to save store: {}", e))?;
-        Ok(())
+        self.secrets.set(KEY_SERVER_URL, serde_json::json!(new_server_url))?;
+        self.secrets.set(KEY_APP_ID, serde_json::json!(app_id))?;
+        self.secrets.set_secret(KEY_API_KEY, &api_key)?;
+        self.secrets.save()
     }
 
     pub fn sync_client(&self) -> Arc<RwLock<GleanOakClient<ReqwestHttpClient>>> {
         Arc::clone(&self.sync_client)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secret_store::MemoryStore;
+    use axum::{extract::State, http::StatusCode, routing::post, Json, Router};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Where the sync client sends refreshes
+    const REFRESH_PATH: &str = "/api/v1/auth/refresh";
+
+    fn service() -> AuthService {
+        service_at("http://127.0.0.1:9")
+    }
+
+    fn service_at(server_url: &str) -> AuthService {
+        AuthService::new(
+            server_url.to_string(),
+            "app".to_string(),
+            "key".to_string(),
+            Arc::new(MemoryStore::default()),
+        )
+    }
+
+    /// JWT that expires `expires_in` seconds from now
+    fn token(expires_in: i64) -> String {
+        let claims = serde_json::json!({ "exp": unix_now().unwrap() + expires_in, "sub": "user-1" });
+        encode(&Header::default(), &claims, &EncodingKey::from_secret(b"test")).unwrap()
+    }
+
+    fn signed_in(user_id: &str, access_token: String) -> AuthResponse {
+        AuthResponse {
+            user_id: user_id.to_string(),
+            access_token,
+            refresh_token: format!("refresh-{}", user_id),
+            apps: Some(vec!["money-insight".to_string()]),
+            is_admin: None,
+        }
+    }
+
+    async fn sign_in(auth: &AuthService, user_id: &str, email: &str) {
+        auth.store_auth_data(&signed_in(user_id, token(3600)), email, "app", "key").await.unwrap();
+    }
+
+    /// Sync server with refresh-token rotation: every refresh token works once
+    #[derive(Clone, Default)]
+    struct MockSyncServer {
+        valid: Arc<Mutex<HashSet<String>>>,
+        requests: Arc<AtomicUsize>,
+    }
+
+    async fn refresh(
+        State(server): State<MockSyncServer>,
+        Json(body): Json<serde_json::Value>,
+    ) -> Result<Json<serde_json::Value>, StatusCode> {
+        let n = server.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        // Keep the request open long enough for the other callers to pile up
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let mut valid = server.valid.lock().unwrap();
+        if !body["refreshToken"].as_str().is_some_and(|t| valid.remove(t)) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let refresh_token = format!("refresh-{}", n);
+        valid.insert(refresh_token.clone());
+        Ok(Json(serde_json::json!({
+            "success": true,
+            "data": { "accessToken": token(3600), "refreshToken": refresh_token },
+        })))
+    }
+
+    async fn start_mock_sync_server(refresh_tokens: &[&str]) -> (String, MockSyncServer) {
+        let server = MockSyncServer::default();
+        server.valid.lock().unwrap().extend(refresh_tokens.iter().map(|t| t.to_string()));
+        let app = Router::new().route(REFRESH_PATH, post(refresh)).with_state(server.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn valid_token_is_authenticated_without_refresh() {
+        let auth = service();
+        sign_in(&auth, "user-1", "one@example.com").await;
+
+        let status = auth.get_auth_status().await;
+
+        assert!(status.is_authenticated);
+        assert_eq!(status.user_id.as_deref(), Some("user-1"));
+        assert_eq!(status.apps, Some(vec!["money-insight".to_string()]));
+        assert!(auth.is_authenticated().await);
+    }
+
+    #[tokio::test]
+    async fn expired_token_without_refresh_token_is_signed_out() {
+        let auth = service();
+        auth.store_auth_data(&signed_in("user-1", token(-60)), "one@example.com", "app", "key").await.unwrap();
+        auth.secrets.delete(KEY_REFRESH_TOKEN).unwrap();
+
+        let status = auth.get_auth_status().await;
+
+        assert!(!status.is_authenticated);
+        assert_eq!(status.user_id, None);
+        assert!(!auth.is_authenticated().await);
+    }
+
+    #[tokio::test]
+    async fn concurrent_status_checks_share_one_refresh() {
+        let (url, server) = start_mock_sync_server(&["refresh-user-1"]).await;
+        let auth = service_at(&url);
+        auth.store_auth_data(&signed_in("user-1", token(-60)), "one@example.com", "app", "key").await.unwrap();
+
+        let checks: Vec<_> = (0..8)
+            .map(|_| {
+                let auth = auth.clone();
+                tokio::spawn(async move { auth.get_auth_status().await })
+            })
+            .collect();
+        for check in checks {
+            assert!(check.await.unwrap().is_authenticated);
+        }
+
+        assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+        assert_eq!(auth.get_refresh_token().await.unwrap(), "refresh-1");
+        assert!(!is_token_expired(&auth.get_access_token().await.unwrap()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn profiles_refresh_separately() {
+        let (url, server) = start_mock_sync_server(&["refresh-user-1", "refresh-user-2"]).await;
+        let auth = service_at(&url);
+        auth.store_auth_data(&signed_in("user-1", token(-60)), "one@example.com", "app", "key").await.unwrap();
+        let first = {
+            let auth = auth.clone();
+            tokio::spawn(async move { auth.refresh_token().await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        auth.switch_profile("Work").unwrap();
+        auth.store_auth_data(&signed_in("user-2", token(-60)), "two@example.com", "app", "key").await.unwrap();
+        assert!(auth.get_auth_status().await.is_authenticated);
+        first.await.unwrap().unwrap();
+
+        assert_eq!(server.requests.load(Ordering::SeqCst), 2);
+        assert!(!is_token_expired(&auth.get_access_token().await.unwrap()).unwrap());
+        auth.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert!(!is_token_expired(&auth.get_access_token().await.unwrap()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn sync_waits_for_confirmation_after_a_profile_switch() {
+        let auth = service();
+        sign_in(&auth, "user-1", "one@example.com").await;
+        assert!(auth.ensure_sync_allowed().is_ok());
+
+        auth.switch_profile("Work").unwrap();
+        sign_in(&auth, "user-2", "two@example.com").await;
+        assert!(auth.ensure_sync_allowed().is_err());
+        auth.confirm_sync_profile().unwrap();
+        assert!(auth.ensure_sync_allowed().is_ok());
+
+        auth.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert!(auth.ensure_sync_allowed().is_err());
+    }
+
+    #[tokio::test]
+    async fn profiles_keep_separate_credentials() {
+        let auth = service();
+        sign_in(&auth, "user-1", "one@example.com").await;
+        auth.switch_profile("Work").unwrap();
+        assert!(auth.get_access_token().await.is_err());
+        sign_in(&auth, "user-2", "two@example.com").await;
+
+        assert_eq!(auth.get_auth_status().await.user_id.as_deref(), Some("user-2"));
+        assert_eq!(auth.profile_email().as_deref(), Some("two@example.com"));
+
+        auth.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!(auth.get_auth_status().await.user_id.as_deref(), Some("user-1"));
+
+        let profiles = auth.list_profiles().unwrap();
+        let names: Vec<_> = profiles.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["default", "Work"]);
+        assert!(profiles.iter().all(|p| p.is_authenticated));
+        assert!(profiles[0].is_active && !profiles[1].is_active);
+    }
+
+    #[tokio::test]
+    async fn logout_only_signs_out_the_active_profile() {
+        let auth = service();
+        sign_in(&auth, "user-1", "one@example.com").await;
+        auth.switch_profile("Work").unwrap();
+        sign_in(&auth, "user-2", "two@example.com").await;
+
+        auth.logout().await.unwrap();
+
+        assert!(!auth.get_auth_status().await.is_authenticated);
+        assert_eq!(auth.profile_email(), None);
+        auth.switch_profile(DEFAULT_PROFILE).unwrap();
+        assert!(auth.get_auth_status().await.is_authenticated);
+        // Sync configuration is shared and survives the logout
+        assert_eq!(auth.get_stored_api_key().unwrap(), "key");
+    }
+
+    #[tokio::test]
+    async fn rejected_refresh_is_not_retried_as_unavailable() {
+        let (url, server) = start_mock_sync_server(&[]).await;
+        let auth = service_at(&url);
+        auth.store_auth_data(&signed_in("user-1", token(-60)), "one@example.com", "app", "key").await.unwrap();
+
+        let error = auth.refresh_token().await.unwrap_err();
+
+        assert!(matches!(error, RefreshError::Rejected(_)), "{:?}", error);
+        assert_eq!(server.requests.load(Ordering::SeqCst), 1);
+        assert!(matches!(service().refresh_token().await, Err(RefreshError::Rejected(_))));
+    }
+
+    #[test]
+    fn refresh_errors_are_classified_by_status_and_reason() {
+        for rejected in ["HTTP 401 Unauthorized", "Refresh token revoked", "status: 403"] {
+            assert!(matches!(classify_refresh_error(rejected.to_string()), RefreshError::Rejected(_)), "{}", rejected);
+        }
+        for unavailable in [
+            "error sending request: connection refused",
+            "HTTP 503 Service Unavailable",
+            "HTTP 429 Too Many Requests",
+            "connect to 10.0.0.4001 timed out",
+        ] {
+            assert!(matches!(classify_refresh_error(unavailable.to_string()), RefreshError::Unavailable(_)), "{}", unavailable);
+        }
+    }
+
+    #[test]
+    fn invalid_profile_names_are_rejected() {
+        let auth = service();
+        assert!(auth.switch_profile("   ").is_err());
+        assert!(auth.switch_profile("a/b").is_err());
+        assert!(auth.switch_profile(&"x".repeat(MAX_PROFILE_NAME_LEN + 1)).is_err());
+        assert_eq!(auth.active_profile().unwrap(), DEFAULT_PROFILE);
+        assert_eq!(auth.list_profiles().unwrap().len(), 1);
+    }
+}
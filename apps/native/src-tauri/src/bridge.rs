@@ -0,0 +1,43 @@
+use futures_util::future::BoxFuture;
+use std::sync::Arc;
+
+use crate::data_store::DesktopChange;
+use crate::throttle::TokenGuessingAlert;
+
+/// What the sync proxy needs to call the sync server on the user's behalf
+pub struct SyncCredentials {
+    pub server_url: String,
+    pub app_id: String,
+    pub api_key: String,
+    pub access_token: String,
+}
+
+/// Desktop-side operations the web server cannot perform on its own,
+/// implemented by the Tauri app so the server stays independent of it
+pub trait DesktopBridge: Send + Sync {
+    /// Sync-server auth status as the desktop app reports it
+    fn auth_status(&self) -> BoxFuture<'_, Result<serde_json::Value, String>>;
+
+    /// Ask the desktop window to run a sync. Progress is reported through
+    /// `sync_started`/`sync_finished` events.
+    fn request_sync(&self) -> Result<(), String>;
+
+    /// Configured sync server, without refreshing any credentials
+    fn sync_server_url(&self) -> Result<String, String>;
+
+    /// Credentials for proxied sync requests, with an access token that has not
+    /// expired. `force_refresh` refreshes the token even if it looks valid.
+    fn sync_credentials(
+        &self,
+        force_refresh: bool,
+    ) -> BoxFuture<'_, Result<SyncCredentials, String>>;
+
+    /// Warn the user that a client is guessing session tokens
+    fn token_guessing_detected(&self, alert: &TokenGuessingAlert);
+
+    /// Apply a row written through the web API to the desktop window's
+    /// database, which is the app's copy of record
+    fn data_written(&self, change: &DesktopChange);
+}
+
+pub type SharedBridge = Arc<dyn DesktopBridge>;
@@ -0,0 +1,602 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::events::ServerEvent;
+use crate::models::{Account, Budget, Category, Debt, DebtSettlement, Record, Transaction};
+
+/// File name of the browser-mode data store inside the app data dir. It is a
+/// copy of the desktop window's database, so the web API can answer before
+/// the window has sent its snapshot.
+pub const DATA_STORE_FILE: &str = "browser-data.json";
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreData {
+    #[serde(default)]
+    transactions: Vec<Transaction>,
+    #[serde(default)]
+    accounts: Vec<Account>,
+    #[serde(default)]
+    categories: Vec<Category>,
+    #[serde(default)]
+    budgets: Vec<Budget>,
+    #[serde(default)]
+    debts: Vec<Debt>,
+    #[serde(default)]
+    debt_settlements: Vec<DebtSettlement>,
+}
+
+/// A record type with a table in the data store
+pub trait Stored: Record {
+    /// Table name used in change events
+    const TABLE: &'static str;
+    /// Table name in the desktop window's IndexedDB
+    const DESKTOP_TABLE: &'static str;
+    fn rows(data: &StoreData) -> &Vec<Self>;
+    fn rows_mut(data: &mut StoreData) -> &mut Vec<Self>;
+}
+
+macro_rules! impl_stored {
+    ($ty:ty, $field:ident, $desktop_table:literal) => {
+        impl Stored for $ty {
+            const TABLE: &'static str = stringify!($field);
+            const DESKTOP_TABLE: &'static str = $desktop_table;
+            fn rows(data: &StoreData) -> &Vec<Self> {
+                &data.$field
+            }
+            fn rows_mut(data: &mut StoreData) -> &mut Vec<Self> {
+                &mut data.$field
+            }
+        }
+    };
+}
+
+impl_stored!(Transaction, transactions, "transactions");
+impl_stored!(Account, accounts, "accounts");
+impl_stored!(Category, categories, "categories");
+impl_stored!(Budget, budgets, "budgets");
+impl_stored!(Debt, debts, "debts");
+impl_stored!(DebtSettlement, debt_settlements, "debtSettlements");
+
+/// A row written in one copy of the data that the other copy has to apply:
+/// desktop edits sent to the web server, and web API edits sent to the desktop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopChange {
+    /// IndexedDB table name
+    pub table: String,
+    pub id: String,
+    /// The row after the change; absent for deletions
+    #[serde(default)]
+    pub row: Option<Value>,
+}
+
+impl DesktopChange {
+    pub fn upsert<T: Stored>(record: &T) -> Self {
+        Self {
+            table: T::DESKTOP_TABLE.to_string(),
+            id: record.id().to_string(),
+            row: serde_json::to_value(record).ok(),
+        }
+    }
+
+    pub fn delete<T: Stored>(id: &str) -> Self {
+        Self {
+            table: T::DESKTOP_TABLE.to_string(),
+            id: id.to_string(),
+            row: None,
+        }
+    }
+}
+
+/// Every served table as read from the desktop window's IndexedDB
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DesktopSnapshot {
+    #[serde(default)]
+    transactions: Vec<Value>,
+    #[serde(default)]
+    accounts: Vec<Value>,
+    #[serde(default)]
+    categories: Vec<Value>,
+    #[serde(default)]
+    budgets: Vec<Value>,
+    #[serde(default)]
+    debts: Vec<Value>,
+    #[serde(default)]
+    debt_settlements: Vec<Value>,
+}
+
+/// Size of the data store, for diagnostics
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoreStats {
+    /// Absent for in-memory stores
+    pub path: Option<PathBuf>,
+    /// Absent until the first write
+    pub size_bytes: Option<u64>,
+    pub rows: BTreeMap<&'static str, usize>,
+}
+
+/// JSON-file backed store for the data served by the embedded web server
+pub struct DataStore {
+    path: Option<PathBuf>,
+    data: Mutex<StoreData>,
+}
+
+impl DataStore {
+    /// Open the store at `path`, starting empty if the file does not exist yet
+    pub fn open(path: PathBuf) -> Result<Self, String> {
+        let data = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse data store: {}", e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => StoreData::default(),
+            Err(e) => return Err(format!("Failed to read data store: {}", e)),
+        };
+        Ok(Self {
+            path: Some(path),
+            data: Mutex::new(data),
+        })
+    }
+
+    /// Store that is never written to disk
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            data: Mutex::new(StoreData::default()),
+        }
+    }
+
+    pub fn list<T: Stored>(&self, predicate: impl Fn(&T) -> bool) -> Vec<T> {
+        let data = self.data.lock().unwrap();
+        T::rows(&data)
+            .iter()
+            .filter(|r| predicate(r))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get<T: Stored>(&self, id: &str) -> Option<T> {
+        let data = self.data.lock().unwrap();
+        T::rows(&data).iter().find(|r| r.id() == id).cloned()
+    }
+
+    /// Debts a settlement counts towards are recomputed in the same write,
+    /// here and in `replace` and `delete`
+    pub fn insert<T: Stored>(&self, record: T) -> Result<T, String> {
+        let mut data = self.data.lock().unwrap();
+        if T::rows(&data).iter().any(|r| r.id() == record.id()) {
+            return Err(format!("{} {} already exists", T::NAME, record.id()));
+        }
+        require_debt(&data, &record)?;
+        let debt_id = record.settles_debt().map(str::to_string);
+        let mut updated = data.clone();
+        T::rows_mut(&mut updated).push(record.clone());
+        settle_debts(&mut updated, [debt_id]);
+        self.save(&mut data, updated)?;
+        Ok(record)
+    }
+
+    /// Replace the stored row with the same id, returning `false` if it does not exist
+    pub fn replace<T: Stored>(&self, record: T) -> Result<bool, String> {
+        let mut data = self.data.lock().unwrap();
+        require_debt(&data, &record)?;
+        let Some(index) = T::rows(&data).iter().position(|r| r.id() == record.id()) else {
+            return Ok(false);
+        };
+        let mut updated = data.clone();
+        let existing = &mut T::rows_mut(&mut updated)[index];
+        let debt_ids =
+            [existing.settles_debt(), record.settles_debt()].map(|id| id.map(str::to_string));
+        *existing = record;
+        settle_debts(&mut updated, debt_ids);
+        self.save(&mut data, updated)?;
+        Ok(true)
+    }
+
+    /// Returns `false` if no row with `id` exists
+    pub fn delete<T: Stored>(&self, id: &str) -> Result<bool, String> {
+        let mut data = self.data.lock().unwrap();
+        let Some(index) = T::rows(&data).iter().position(|r| r.id() == id) else {
+            return Ok(false);
+        };
+        let mut updated = data.clone();
+        let removed = T::rows_mut(&mut updated).remove(index);
+        settle_debts(&mut updated, [removed.settles_debt().map(str::to_string)]);
+        self.save(&mut data, updated)?;
+        Ok(true)
+    }
+
+    /// Replace every table with the desktop window's rows. Rows this server
+    /// cannot parse are left out; returns how many were.
+    pub fn load_desktop_snapshot(&self, snapshot: DesktopSnapshot) -> Result<usize, String> {
+        fn parse<T: Stored>(rows: Vec<Value>, skipped: &mut usize) -> Vec<T> {
+            let total = rows.len();
+            let parsed: Vec<T> = rows
+                .into_iter()
+                .filter_map(|row| serde_json::from_value(row).ok())
+                .collect();
+            *skipped += total - parsed.len();
+            parsed
+        }
+
+        let mut skipped = 0;
+        let data = StoreData {
+            transactions: parse(snapshot.transactions, &mut skipped),
+            accounts: parse(snapshot.accounts, &mut skipped),
+            categories: parse(snapshot.categories, &mut skipped),
+            budgets: parse(snapshot.budgets, &mut skipped),
+            debts: parse(snapshot.debts, &mut skipped),
+            debt_settlements: parse(snapshot.debt_settlements, &mut skipped),
+        };
+        let mut current = self.data.lock().unwrap();
+        self.save(&mut current, data)?;
+        Ok(skipped)
+    }
+
+    /// Apply rows changed in the desktop window, returning an event for each
+    /// row that differs from this copy. Rows already up to date, such as the
+    /// desktop echoing back a web API write, produce no event.
+    pub fn apply_desktop_changes(
+        &self,
+        changes: Vec<DesktopChange>,
+    ) -> Result<Vec<ServerEvent>, String> {
+        let mut data = self.data.lock().unwrap();
+        let mut updated = data.clone();
+        let mut events = Vec::new();
+        for change in changes {
+            let event = match change.table.as_str() {
+                "transactions" => apply_change::<Transaction>(&mut updated, change),
+                "accounts" => apply_change::<Account>(&mut updated, change),
+                "categories" => apply_change::<Category>(&mut updated, change),
+                "budgets" => apply_change::<Budget>(&mut updated, change),
+                "debts" => apply_change::<Debt>(&mut updated, change),
+                "debtSettlements" => apply_change::<DebtSettlement>(&mut updated, change),
+                other => Err(format!("Unknown table {}", other)),
+            };
+            match event {
+                Ok(Some(event)) => events.push(event),
+                Ok(None) => {}
+                Err(e) => eprintln!("Skipped desktop change: {}", e),
+            }
+        }
+        if !events.is_empty() {
+            self.save(&mut data, updated)?;
+        }
+        Ok(events)
+    }
+
+    /// Row counts per table and the size of the file on disk
+    pub fn stats(&self) -> StoreStats {
+        fn count<T: Stored>(data: &StoreData) -> (&'static str, usize) {
+            (T::TABLE, T::rows(data).len())
+        }
+
+        let rows = {
+            let data = self.data.lock().unwrap();
+            BTreeMap::from([
+                count::<Transaction>(&data),
+                count::<Account>(&data),
+                count::<Category>(&data),
+                count::<Budget>(&data),
+                count::<Debt>(&data),
+                count::<DebtSettlement>(&data),
+            ])
+        };
+        StoreStats {
+            path: self.path.clone(),
+            size_bytes: self
+                .path
+                .as_ref()
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|metadata| metadata.len()),
+            rows,
+        }
+    }
+
+    /// Make `updated` the live data once it is on disk, so a failed write
+    /// leaves readers with what is actually stored
+    fn save(&self, data: &mut StoreData, updated: StoreData) -> Result<(), String> {
+        self.persist(&updated)?;
+        *data = updated;
+        Ok(())
+    }
+
+    // Write to a temp file and rename so a crash never leaves a truncated store
+    fn persist(&self, data: &StoreData) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let json = serde_json::to_string(data)
+            .map_err(|e| format!("Failed to serialize data store: {}", e))?;
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, json)
+            .map_err(|e| format!("Failed to write data store: {}", e))?;
+        std::fs::rename(&tmp_path, path).map_err(|e| format!("Failed to save data store: {}", e))
+    }
+}
+
+/// Reject a row that counts towards a debt that does not exist
+fn require_debt<T: Stored>(data: &StoreData, record: &T) -> Result<(), String> {
+    match record.settles_debt() {
+        Some(debt_id) if !data.debts.iter().any(|d| d.id == debt_id) => {
+            Err(format!("Debt {} not found", debt_id))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Recompute the listed debts from their settlements
+fn settle_debts<const N: usize>(data: &mut StoreData, debt_ids: [Option<String>; N]) {
+    for debt_id in debt_ids.into_iter().flatten() {
+        let settlements: Vec<&DebtSettlement> = data
+            .debt_settlements
+            .iter()
+            .filter(|s| s.debt_id == debt_id)
+            .collect();
+        if let Some(debt) = data.debts.iter_mut().find(|d| d.id == debt_id) {
+            if let Some(settled) = debt.settle(&settlements) {
+                *debt = settled;
+            }
+        }
+    }
+}
+
+fn apply_change<T: Stored>(
+    data: &mut StoreData,
+    change: DesktopChange,
+) -> Result<Option<ServerEvent>, String> {
+    let rows = T::rows_mut(data);
+    let index = rows.iter().position(|r| r.id() == change.id);
+    let Some(row) = change.row else {
+        return Ok(index.map(|i| {
+            rows.remove(i);
+            ServerEvent::deleted::<T>(&change.id)
+        }));
+    };
+    let record: T = serde_json::from_value(row)
+        .map_err(|e| format!("Invalid {} {}: {}", T::NAME, change.id, e))?;
+    if record.id() != change.id {
+        return Err(format!(
+            "{} {} sent with id {}",
+            T::NAME,
+            record.id(),
+            change.id
+        ));
+    }
+    match index {
+        Some(i) if serde_json::to_value(&rows[i]).ok() == serde_json::to_value(&record).ok() => {
+            Ok(None)
+        }
+        Some(i) => {
+            let event = ServerEvent::updated(&record);
+            rows[i] = record;
+            Ok(Some(event))
+        }
+        None => {
+            let event = ServerEvent::created(&record);
+            rows.push(record);
+            Ok(Some(event))
+        }
+    }
+}
+
+pub type SharedDataStore = Arc<DataStore>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::ChangeAction;
+    use serde_json::json;
+
+    /// An account row as the desktop window stores it, with a field this server does not know
+    fn account_row(id: &str, name: &str) -> Value {
+        json!({
+            "id": id,
+            "name": name,
+            "initialBalance": 0,
+            "currency": "VND",
+            "createdAt": "2026-01-01T00:00:00.000Z",
+            "updatedAt": "2026-01-01T00:00:00.000Z",
+            "syncVersion": 1,
+            "syncedAt": null,
+            "sortOrder": 3,
+        })
+    }
+
+    fn change(id: &str, row: Option<Value>) -> DesktopChange {
+        DesktopChange {
+            table: "accounts".to_string(),
+            id: id.to_string(),
+            row,
+        }
+    }
+
+    fn actions(events: &[ServerEvent]) -> Vec<(ChangeAction, String)> {
+        events
+            .iter()
+            .map(|event| match event {
+                ServerEvent::DataChanged { action, id, .. } => (*action, id.clone()),
+                other => panic!("unexpected event {:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snapshot_replaces_the_copy_and_skips_unreadable_rows() {
+        let store = DataStore::in_memory();
+        store
+            .insert(
+                Account::create(
+                    "stale".into(),
+                    serde_json::from_value(json!({"name": "Old", "currency": "VND"})).unwrap(),
+                )
+                .unwrap(),
+            )
+            .unwrap();
+
+        let snapshot = serde_json::from_value(json!({
+            "accounts": [account_row("a1", "Cash"), {"id": "broken"}],
+            "debtSettlements": [],
+        }))
+        .unwrap();
+
+        assert_eq!(store.load_desktop_snapshot(snapshot).unwrap(), 1);
+        let accounts: Vec<Account> = store.list(|_| true);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, "Cash");
+    }
+
+    #[test]
+    fn desktop_changes_produce_events_only_when_rows_differ() {
+        let store = DataStore::in_memory();
+
+        let events = store
+            .apply_desktop_changes(vec![change("a1", Some(account_row("a1", "Cash")))])
+            .unwrap();
+        assert!(matches!(actions(&events)[..], [(ChangeAction::Created, _)]));
+
+        // The desktop echoing back the row it was sent changes nothing
+        let events = store
+            .apply_desktop_changes(vec![change("a1", Some(account_row("a1", "Cash")))])
+            .unwrap();
+        assert!(events.is_empty());
+
+        let events = store
+            .apply_desktop_changes(vec![
+                change("a1", Some(account_row("a1", "Wallet"))),
+                change("missing", None),
+                change("a1", None),
+            ])
+            .unwrap();
+        assert!(matches!(
+            actions(&events)[..],
+            [(ChangeAction::Updated, _), (ChangeAction::Deleted, _)]
+        ));
+        assert!(store.get::<Account>("a1").is_none());
+    }
+
+    #[test]
+    fn invalid_desktop_changes_are_skipped() {
+        let store = DataStore::in_memory();
+        let events = store
+            .apply_desktop_changes(vec![
+                DesktopChange {
+                    table: "importBatches".to_string(),
+                    id: "b1".to_string(),
+                    row: Some(json!({})),
+                },
+                change("a1", Some(account_row("a2", "Cash"))),
+                change("a3", Some(account_row("a3", "Cash"))),
+            ])
+            .unwrap();
+        assert!(matches!(&actions(&events)[..], [(ChangeAction::Created, id)] if id == "a3"));
+    }
+
+    fn settlement(id: &str, debt_id: &str, amount: f64) -> DebtSettlement {
+        let input = serde_json::from_value(json!({
+            "debtId": debt_id,
+            "transactionId": "t1",
+            "accountId": "a1",
+            "amount": amount,
+            "settledAt": "2026-02-01T00:00:00.000Z",
+        }))
+        .unwrap();
+        DebtSettlement::create(id.into(), input).unwrap()
+    }
+
+    #[test]
+    fn settlements_update_their_debt_in_the_same_write() {
+        let store = DataStore::in_memory();
+        let input = serde_json::from_value(json!({
+            "name": "Loan",
+            "debtType": "receivable",
+            "counterpartyName": "Binh",
+            "accountId": "a1",
+            "currency": "VND",
+            "principalAmount": 100.0,
+            "originatedAt": "2026-01-01T00:00:00.000Z",
+        }))
+        .unwrap();
+        store
+            .insert(Debt::create("d1".into(), input).unwrap())
+            .unwrap();
+
+        store.insert(settlement("s1", "d1", 40.0)).unwrap();
+        let debt = store.get::<Debt>("d1").unwrap();
+        assert_eq!(
+            (
+                debt.settled_amount,
+                debt.remaining_amount,
+                debt.is_completed
+            ),
+            (40.0, 60.0, false)
+        );
+        assert!(debt.synced_at.is_none());
+
+        store.replace(settlement("s1", "d1", 100.0)).unwrap();
+        let debt = store.get::<Debt>("d1").unwrap();
+        assert_eq!((debt.remaining_amount, debt.is_completed), (0.0, true));
+        assert_eq!(
+            debt.completed_at.as_deref(),
+            Some("2026-02-01T00:00:00.000Z")
+        );
+
+        store.delete::<DebtSettlement>("s1").unwrap();
+        let debt = store.get::<Debt>("d1").unwrap();
+        assert_eq!(
+            (
+                debt.settled_amount,
+                debt.remaining_amount,
+                debt.is_completed
+            ),
+            (0.0, 100.0, false)
+        );
+    }
+
+    #[test]
+    fn failed_writes_leave_the_data_unchanged() {
+        let dir = std::env::temp_dir().join(format!("mi-data-{}", crate::session::random_hex(8)));
+        let path = dir.join(DATA_STORE_FILE);
+        let store = DataStore::open(path.clone()).unwrap();
+        store
+            .apply_desktop_changes(vec![change("a1", Some(account_row("a1", "Cash")))])
+            .unwrap();
+        // The store writes through this temp file, so every write now fails
+        std::fs::create_dir(path.with_extension("json.tmp")).unwrap();
+
+        let input =
+            |name: &str| serde_json::from_value(json!({"name": name, "currency": "VND"})).unwrap();
+        let added = Account::create("a2".into(), input("Bank")).unwrap();
+        assert!(store.insert(added).is_err());
+        let renamed = store
+            .get::<Account>("a1")
+            .unwrap()
+            .update(input("Wallet"))
+            .unwrap();
+        assert!(store.replace(renamed).is_err());
+        assert!(store.delete::<Account>("a1").is_err());
+        assert!(store
+            .apply_desktop_changes(vec![change("a3", Some(account_row("a3", "Bank")))])
+            .is_err());
+
+        let accounts: Vec<Account> = store.list(|_| true);
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(accounts[0].name, "Cash");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn settlements_of_unknown_debts_are_rejected() {
+        let store = DataStore::in_memory();
+        assert!(store.insert(settlement("s1", "missing", 10.0)).is_err());
+        assert!(store.get::<DebtSettlement>("s1").is_none());
+    }
+}
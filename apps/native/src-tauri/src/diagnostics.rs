@@ -0,0 +1,120 @@
+use axum::{extract::State, http::HeaderMap, Json};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::api::ApiError;
+use crate::data_store::StoreStats;
+use crate::events::LastSync;
+use crate::session::SessionScope;
+use crate::web_server::{validate_token, ApiResponse, AppState};
+
+/// How long the sync server gets to answer the reachability probe
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Everything support scripts need to triage a report without opening the app
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Diagnostics {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    /// Auth status as the desktop app reports it
+    pub auth: Option<serde_json::Value>,
+    /// Why the auth status could not be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auth_error: Option<String>,
+    pub sync_server: SyncServerStatus,
+    pub last_sync: Option<LastSync>,
+    pub storage: StoreStats,
+    pub server: ServerStats,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncServerStatus {
+    pub url: Option<String>,
+    /// Whether the server answered at all, whatever the status code
+    pub reachable: bool,
+    pub status: Option<u16>,
+    pub latency_ms: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStats {
+    pub tls: bool,
+    pub active_sessions: usize,
+    pub access_tokens: usize,
+    pub sse_subscribers: usize,
+}
+
+pub async fn diagnostics_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Diagnostics>>, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+
+    let (auth, sync_server) = tokio::join!(state.bridge.auth_status(), probe_sync_server(&state));
+    let (auth, auth_error) = match auth {
+        Ok(auth) => (Some(auth), None),
+        Err(e) => (None, Some(e)),
+    };
+    Ok(Json(ApiResponse::success(Diagnostics {
+        version: APP_VERSION,
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        auth,
+        auth_error,
+        sync_server,
+        last_sync: state.events.last_sync(),
+        storage: state.data_store.stats(),
+        server: ServerStats {
+            tls: state.secure_cookies,
+            active_sessions: state.session_manager.list_sessions().len(),
+            access_tokens: state.access_tokens.list().len(),
+            sse_subscribers: state.sse_subscribers.load(Ordering::Relaxed),
+        },
+    })))
+}
+
+async fn probe_sync_server(state: &AppState) -> SyncServerStatus {
+    let url = match state.bridge.sync_server_url() {
+        Ok(url) => url,
+        Err(e) => {
+            return SyncServerStatus {
+                url: None,
+                reachable: false,
+                status: None,
+                latency_ms: None,
+                error: Some(e),
+            }
+        }
+    };
+
+    let started = Instant::now();
+    let result = state
+        .http_client
+        .get(&url)
+        .timeout(PROBE_TIMEOUT)
+        .send()
+        .await;
+    let latency_ms = Some(started.elapsed().as_millis() as u64);
+    match result {
+        Ok(response) => SyncServerStatus {
+            url: Some(url),
+            reachable: true,
+            status: Some(response.status().as_u16()),
+            latency_ms,
+            error: None,
+        },
+        Err(e) => SyncServerStatus {
+            url: Some(url),
+            reachable: false,
+            status: None,
+            latency_ms,
+            error: Some(e.to_string()),
+        },
+    }
+}
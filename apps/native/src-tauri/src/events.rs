@@ -0,0 +1,140 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::data_store::Stored;
+use crate::models::now_iso;
+
+/// Name of the Tauri event server events are forwarded to the desktop window as
+pub const TAURI_EVENT: &str = "server-event";
+
+/// Events a subscriber may fall behind by before it starts missing events
+const EVENT_BUFFER: usize = 64;
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+/// Event broadcast to every SSE subscriber and the desktop window
+#[derive(Debug, Clone, Serialize)]
+#[serde(
+    tag = "type",
+    rename_all = "snake_case",
+    rename_all_fields = "camelCase"
+)]
+pub enum ServerEvent {
+    /// A row in one of the data tables was created, updated or deleted
+    DataChanged {
+        table: &'static str,
+        action: ChangeAction,
+        id: String,
+        /// The row after the change; absent for deletions
+        #[serde(skip_serializing_if = "Option::is_none")]
+        record: Option<serde_json::Value>,
+    },
+    SyncStarted,
+    SyncFinished {
+        success: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    AuthChanged {
+        is_authenticated: bool,
+    },
+}
+
+impl ServerEvent {
+    pub fn created<T: Stored>(record: &T) -> Self {
+        Self::changed(ChangeAction::Created, record)
+    }
+
+    pub fn updated<T: Stored>(record: &T) -> Self {
+        Self::changed(ChangeAction::Updated, record)
+    }
+
+    pub fn deleted<T: Stored>(id: &str) -> Self {
+        Self::DataChanged {
+            table: T::TABLE,
+            action: ChangeAction::Deleted,
+            id: id.to_string(),
+            record: None,
+        }
+    }
+
+    fn changed<T: Stored>(action: ChangeAction, record: &T) -> Self {
+        Self::DataChanged {
+            table: T::TABLE,
+            action,
+            id: record.id().to_string(),
+            record: serde_json::to_value(record).ok(),
+        }
+    }
+
+    /// SSE event name, matching the serialized `type` tag
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DataChanged { .. } => "data_changed",
+            Self::SyncStarted => "sync_started",
+            Self::SyncFinished { .. } => "sync_finished",
+            Self::AuthChanged { .. } => "auth_changed",
+        }
+    }
+}
+
+/// Outcome of the most recent sync, kept for diagnostics
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LastSync {
+    pub finished_at: String,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+/// Broadcast channel shared by the web servers and Tauri commands
+pub struct EventBus {
+    tx: broadcast::Sender<ServerEvent>,
+    last_sync: Mutex<Option<LastSync>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (tx, _) = broadcast::channel(EVENT_BUFFER);
+        Self {
+            tx,
+            last_sync: Mutex::new(None),
+        }
+    }
+
+    /// Send `event` to all current subscribers. Dropped if nobody is listening.
+    pub fn publish(&self, event: ServerEvent) {
+        if let ServerEvent::SyncFinished { success, error } = &event {
+            *self.last_sync.lock().unwrap() = Some(LastSync {
+                finished_at: now_iso(),
+                success: *success,
+                error: error.clone(),
+            });
+        }
+        let _ = self.tx.send(event);
+    }
+
+    /// The last sync reported since the app started
+    pub fn last_sync(&self) -> Option<LastSync> {
+        self.last_sync.lock().unwrap().clone()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.tx.subscribe()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub type SharedEventBus = Arc<EventBus>;
@@ -0,0 +1,310 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::web_server::{ApiResponse, AppState, DEV_SERVER_PORT};
+
+/// Hosts and origins the server answers to
+///
+/// Rejecting unknown `Host` headers defeats DNS rebinding, where an attacker's
+/// domain is re-pointed at 127.0.0.1 so their page becomes "same-origin" with
+/// the server. Rejecting unknown `Origin` headers stops other websites from
+/// talking to the server from the user's browser.
+#[derive(Debug, Clone)]
+pub struct RequestGuard {
+    /// Accepted `Host` values, including the port. `None` accepts any host.
+    hosts: Option<Vec<String>>,
+    /// Origins allowed to make requests
+    origins: Vec<String>,
+}
+
+impl RequestGuard {
+    /// Allow the server's own address, under the loopback names when bound to
+    /// loopback, plus the Vite dev server for the loopback server
+    pub fn new(addr: SocketAddr, tls: bool) -> Self {
+        let port = addr.port();
+        let scheme = if tls { "https" } else { "http" };
+        let names: Vec<String> = match addr.ip() {
+            ip if ip.is_loopback() => vec!["localhost".into(), "127.0.0.1".into(), "[::1]".into()],
+            IpAddr::V6(ip) => vec![format!("[{}]", ip)],
+            ip => vec![ip.to_string()],
+        };
+
+        let hosts = names
+            .iter()
+            .map(|name| format!("{}:{}", name, port))
+            .collect();
+        let mut origins: Vec<String> = names
+            .iter()
+            .map(|name| format!("{}://{}:{}", scheme, name, port))
+            .collect();
+        if addr.ip().is_loopback() {
+            origins.extend(
+                ["localhost", "127.0.0.1"]
+                    .iter()
+                    .map(|name| format!("http://{}:{}", name, DEV_SERVER_PORT)),
+            );
+        }
+
+        Self {
+            hosts: Some(hosts),
+            origins,
+        }
+    }
+
+    /// Guard for the Unix socket server. Browsers cannot connect to a Unix
+    /// socket, so DNS rebinding does not apply and any `Host` is accepted;
+    /// requests carrying an `Origin` are still refused.
+    pub fn unix_socket() -> Self {
+        Self {
+            hosts: None,
+            origins: Vec::new(),
+        }
+    }
+
+    /// Allowed origins for the CORS layer
+    pub fn origins(&self) -> impl Iterator<Item = HeaderValue> + '_ {
+        self.origins
+            .iter()
+            .filter_map(|origin| HeaderValue::from_str(origin).ok())
+    }
+
+    /// Check the Host, Origin and fetch metadata headers of `request`
+    pub fn check(&self, request: &Request) -> Result<(), &'static str> {
+        let headers = request.headers();
+
+        // HTTP/2 carries the host in the :authority pseudo-header instead
+        if let Some(hosts) = &self.hosts {
+            let host = headers
+                .get(header::HOST)
+                .and_then(|v| v.to_str().ok())
+                .or_else(|| request.uri().authority().map(|a| a.as_str()))
+                .ok_or("Missing Host header")?;
+            if !hosts.iter().any(|h| h.eq_ignore_ascii_case(host)) {
+                return Err("Unrecognized Host header");
+            }
+        }
+
+        if let Some(origin) = headers.get(header::ORIGIN) {
+            let origin = origin
+                .to_str()
+                .map_err(|_| "Cross-origin request rejected")?;
+            if !self.origins.iter().any(|o| o.eq_ignore_ascii_case(origin)) {
+                return Err("Cross-origin request rejected");
+            }
+            return Ok(());
+        }
+
+        // Requests without Origin (e.g. <img> or <script> tags) still carry fetch
+        // metadata. Only top-level navigations may come from another site.
+        let fetch_header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let cross_site = fetch_header("sec-fetch-site") == Some("cross-site");
+        let navigation =
+            fetch_header("sec-fetch-mode") == Some("navigate") && request.method() == Method::GET;
+        if cross_site && !navigation {
+            return Err("Cross-origin request rejected");
+        }
+        Ok(())
+    }
+}
+
+/// Middleware rejecting requests that fail the [`RequestGuard`] checks
+pub async fn guard_requests(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(message) = state.guard.check(&request) {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::<()>::error(message)),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    fn loopback() -> RequestGuard {
+        RequestGuard::new(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 25096), false)
+    }
+
+    fn lan() -> RequestGuard {
+        RequestGuard::new(
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), 25096),
+            true,
+        )
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder().uri("/api/health");
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn accepts_loopback_hosts() {
+        let guard = loopback();
+        for host in [
+            "localhost:25096",
+            "127.0.0.1:25096",
+            "[::1]:25096",
+            "LOCALHOST:25096",
+        ] {
+            assert_eq!(guard.check(&request(&[("host", host)])), Ok(()), "{}", host);
+        }
+    }
+
+    #[test]
+    fn rejects_rebound_hosts() {
+        let guard = loopback();
+        for host in [
+            "attacker.example:25096",
+            "localhost.attacker.example:25096",
+            "localhost",
+        ] {
+            assert_eq!(
+                guard.check(&request(&[("host", host)])),
+                Err("Unrecognized Host header"),
+                "{}",
+                host
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_other_ports() {
+        assert_eq!(
+            loopback().check(&request(&[("host", "localhost:8080")])),
+            Err("Unrecognized Host header")
+        );
+    }
+
+    #[test]
+    fn rejects_missing_host() {
+        assert_eq!(loopback().check(&request(&[])), Err("Missing Host header"));
+    }
+
+    #[test]
+    fn uses_authority_when_host_header_is_absent() {
+        let request = Request::builder()
+            .uri("https://192.168.1.20:25096/api/health")
+            .body(Body::empty())
+            .unwrap();
+        assert_eq!(lan().check(&request), Ok(()));
+    }
+
+    #[test]
+    fn accepts_own_and_dev_origins() {
+        let guard = loopback();
+        for origin in [
+            "http://localhost:25096",
+            "http://127.0.0.1:25096",
+            "http://localhost:1420",
+            "http://127.0.0.1:1420",
+        ] {
+            let request = request(&[("host", "localhost:25096"), ("origin", origin)]);
+            assert_eq!(guard.check(&request), Ok(()), "{}", origin);
+        }
+    }
+
+    #[test]
+    fn rejects_foreign_origins() {
+        let guard = loopback();
+        for origin in [
+            "https://attacker.example",
+            "http://localhost:3000",
+            "https://localhost:25096",
+            "null",
+        ] {
+            let request = request(&[("host", "localhost:25096"), ("origin", origin)]);
+            assert_eq!(
+                guard.check(&request),
+                Err("Cross-origin request rejected"),
+                "{}",
+                origin
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_cross_site_subresource_requests() {
+        let request = request(&[
+            ("host", "localhost:25096"),
+            ("sec-fetch-site", "cross-site"),
+            ("sec-fetch-mode", "no-cors"),
+        ]);
+        assert_eq!(
+            loopback().check(&request),
+            Err("Cross-origin request rejected")
+        );
+    }
+
+    #[test]
+    fn allows_cross_site_navigation() {
+        let request = request(&[
+            ("host", "localhost:25096"),
+            ("sec-fetch-site", "cross-site"),
+            ("sec-fetch-mode", "navigate"),
+        ]);
+        assert_eq!(loopback().check(&request), Ok(()));
+    }
+
+    #[test]
+    fn lan_server_only_answers_to_its_address() {
+        let guard = lan();
+        let own = request(&[
+            ("host", "192.168.1.20:25096"),
+            ("origin", "https://192.168.1.20:25096"),
+        ]);
+        assert_eq!(guard.check(&own), Ok(()));
+
+        let dev = request(&[
+            ("host", "192.168.1.20:25096"),
+            ("origin", "http://localhost:1420"),
+        ]);
+        assert_eq!(guard.check(&dev), Err("Cross-origin request rejected"));
+
+        let loopback_host = request(&[("host", "localhost:25096")]);
+        assert_eq!(guard.check(&loopback_host), Err("Unrecognized Host header"));
+    }
+
+    #[test]
+    fn formats_ipv6_lan_addresses_in_brackets() {
+        let addr = SocketAddr::new(
+            IpAddr::V6(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)),
+            25096,
+        );
+        let guard = RequestGuard::new(addr, true);
+        let request = request(&[
+            ("host", "[fe80::1]:25096"),
+            ("origin", "https://[fe80::1]:25096"),
+        ]);
+        assert_eq!(guard.check(&request), Ok(()));
+    }
+
+    #[test]
+    fn unix_socket_accepts_any_host_but_no_origin() {
+        let guard = RequestGuard::unix_socket();
+        assert_eq!(guard.check(&request(&[("host", "localhost")])), Ok(()));
+        assert_eq!(guard.check(&request(&[])), Ok(()));
+
+        let cross_origin = request(&[("host", "localhost"), ("origin", "http://localhost:25096")]);
+        assert_eq!(
+            guard.check(&cross_origin),
+            Err("Cross-origin request rejected")
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, UdpSocket};
+use std::sync::Arc;
+use tokio_rustls::rustls::{
+    self,
+    pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+};
+use tokio_rustls::TlsAcceptor;
+
+/// Self-signed certificate for LAN sharing, generated fresh on every start
+pub struct SelfSignedCert {
+    cert_der: CertificateDer<'static>,
+    key_der: PrivatePkcs8KeyDer<'static>,
+    /// SHA-256 of the DER certificate, uppercase hex without separators
+    pub fingerprint: String,
+}
+
+impl SelfSignedCert {
+    /// Generate a certificate valid for `ip` and `localhost`
+    pub fn generate(ip: IpAddr) -> Result<Self, String> {
+        let certified =
+            rcgen::generate_simple_self_signed(vec![ip.to_string(), "localhost".to_string()])
+                .map_err(|e| format!("Failed to generate certificate: {}", e))?;
+        let cert_der = certified.cert.der().clone();
+        let fingerprint = hex::encode_upper(Sha256::digest(&cert_der));
+        Ok(Self {
+            cert_der,
+            key_der: PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der()),
+            fingerprint,
+        })
+    }
+
+    pub fn tls_acceptor(&self) -> Result<TlsAcceptor, String> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let mut config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Failed to configure TLS: {}", e))?
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![self.cert_der.clone()],
+                PrivateKeyDer::Pkcs8(self.key_der.clone_key()),
+            )
+            .map_err(|e| format!("Failed to configure TLS: {}", e))?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(TlsAcceptor::from(Arc::new(config)))
+    }
+}
+
+/// Fingerprint in the colon-separated form browsers show in certificate details
+pub fn display_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).into_owned())
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Fingerprint as pinned in a pairing link, in the form of
+/// [`SelfSignedCert::fingerprint`] whether or not it has separators
+pub fn normalize_fingerprint(fingerprint: &str) -> String {
+    fingerprint
+        .chars()
+        .filter(|c| *c != ':')
+        .collect::<String>()
+        .to_uppercase()
+}
+
+/// Address of the interface used for the default route, i.e. the one other
+/// devices on the same Wi-Fi can reach. Connecting a UDP socket sends no packets.
+pub fn lan_ip() -> Result<IpAddr, String> {
+    let socket =
+        UdpSocket::bind(("0.0.0.0", 0)).map_err(|e| format!("Failed to open socket: {}", e))?;
+    socket
+        .connect(("192.0.2.1", 9))
+        .map_err(|e| format!("No network connection: {}", e))?;
+    let ip = socket
+        .local_addr()
+        .map_err(|e| format!("Failed to read local address: {}", e))?
+        .ip();
+    if ip.is_loopback() || ip.is_unspecified() {
+        return Err("No LAN interface available".to_string());
+    }
+    Ok(ip)
+}
+
+/// Render `data` as an SVG QR code
+pub fn qr_svg(data: &str) -> Result<String, String> {
+    let code = qrcode::QrCode::new(data.as_bytes())
+        .map_err(|e| format!("Failed to encode QR code: {}", e))?;
+    Ok(code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(256, 256)
+        .build())
+}
@@ -1,206 +1,860 @@
-mod session;
-mod web_server;
-mod auth;
-mod shared_auth;
-mod shared_sync;
-
-use auth::{AuthService, AuthResponse, AuthStatus};
-use session::{SessionManager, SharedSessionManager};
-use std::sync::{Arc, Mutex};
-use tauri::Manager;
-use web_server::{ServerHandle, WEB_SERVER_PORT};
-
-pub struct AppState {
-    auth: Arc<Mutex<AuthService>>,
-}
-
-struct WebServerState {
-    handle: Mutex<Option<ServerHandle>>,
-}
-
-// Auth commands
-#[tauri::command]
-async fn auth_configure_sync(
-    server_url: Option<String>,
-    app_id: Option<String>,
-    api_key: Option<String>,
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-) -> Result<(), String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    auth.configure_sync(&app_handle, server_url, app_id, api_key).await
-}
-
-#[tauri::command]
-async fn auth_register(
-    username: String,
-    email: String,
-    password: String,
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-) -> Result<AuthResponse, String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    auth.register(&app_handle, username, email, password).await
-}
-
-#[tauri::command]
-async fn auth_login(
-    email: String,
-    password: String,
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
-) -> Result<AuthResponse, String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    let response = auth.login(&app_handle, email.clone(), password).await?;
-    let status = auth.get_auth_status(&app_handle).await;
-    auth_status_holder.update(shared_auth::SharedAuthStatus {
-        is_authenticated: status.is_authenticated,
-        user_id: status.user_id,
-        username: None,
-        email: Some(email),
-        apps: status.apps,
-        is_admin: status.is_admin,
-        server_url: status.server_url,
-    });
-    Ok(response)
-}
-
-#[tauri::command]
-async fn auth_logout(
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
-) -> Result<(), String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    auth.logout(&app_handle).await?;
-    auth_status_holder.clear();
-    Ok(())
-}
-
-#[tauri::command]
-async fn auth_get_status(
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
-) -> Result<AuthStatus, String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    let status = auth.get_auth_status(&app_handle).await;
-    auth_status_holder.update(shared_auth::SharedAuthStatus {
-        is_authenticated: status.is_authenticated,
-        user_id: status.user_id.clone(),
-        username: None,
-        email: None,
-        apps: status.apps.clone(),
-        is_admin: status.is_admin,
-        server_url: status.server_url.clone(),
-    });
-    Ok(status)
-}
-
-#[tauri::command]
-async fn auth_is_authenticated(
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-) -> Result<bool, String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    Ok(auth.is_authenticated(&app_handle).await)
-}
-
-#[tauri::command]
-async fn auth_get_access_token(
-    app_handle: tauri::AppHandle,
-    state: tauri::State<'_, AppState>,
-) -> Result<String, String> {
-    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
-    auth.get_access_token(&app_handle).await
-}
-
-// Browser mode commands
-#[tauri::command]
-fn open_in_browser(
-    session_manager: tauri::State<SharedSessionManager>,
-    web_state: tauri::State<WebServerState>,
-) -> Result<String, String> {
-    let mut handle_guard = web_state.handle.lock().map_err(|e| e.to_string())?;
-
-    if handle_guard.is_none() {
-        let handle = web_server::start_web_server(session_manager.inner().clone());
-        *handle_guard = Some(handle);
-    }
-
-    let token = handle_guard.as_ref().map(|h| h.token.clone()).ok_or("Failed to get session token")?;
-
-    let is_dev_mode = std::env::var("TAURI_DEV_HOST").is_ok() || std::env::var("CARGO_MANIFEST_DIR").is_ok();
-    let browser_port = if is_dev_mode { 1420 } else { WEB_SERVER_PORT };
-    let url = format!("http://localhost:{}?session={}", browser_port, token);
-
-    println!("Browser sync started: {}", url);
-    Ok(url)
-}
-
-#[tauri::command]
-fn stop_browser_server(web_state: tauri::State<WebServerState>) -> Result<(), String> {
-    let mut handle_guard = web_state.handle.lock().map_err(|e| e.to_string())?;
-    if let Some(handle) = handle_guard.take() {
-        web_server::stop_web_server(&handle);
-    }
-    Ok(())
-}
-
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_store::Builder::new().build())
-        .setup(|app| {
-            let _ = dotenvy::dotenv();
-
-            let sync_server_url = std::env::var("SYNC_SERVER_URL")
-                .unwrap_or_else(|_| "http://localhost:3000".to_string());
-            let sync_center_app_id = std::env::var("SYNC_CENTER_APP_ID")
-                .unwrap_or_else(|_| "money-insight".to_string());
-            let sync_center_api_key = std::env::var("SYNC_CENTER_API_KEY")
-                .unwrap_or_else(|_| "your_api_key_here".to_string());
-
-            // Initialize auth service
-            let auth = Arc::new(Mutex::new(AuthService::new(
-                sync_server_url,
-                sync_center_app_id,
-                sync_center_api_key,
-            )));
-
-            let app_state = AppState { auth };
-            app.handle().manage(app_state);
-
-            // Initialize session manager
-            let session_manager: SharedSessionManager = Arc::new(SessionManager::new());
-            app.handle().manage(session_manager);
-
-            // Initialize web server state
-            app.handle().manage(WebServerState { handle: Mutex::new(None) });
-
-            // Initialize shared status holders
-            app.handle().manage(shared_auth::create_auth_status_holder());
-            app.handle().manage(shared_sync::create_sync_status_holder());
-
-            println!("[MoneyInsight] Application initialized with sync support");
-
-            Ok(())
-        })
-        .invoke_handler(tauri::generate_handler![
-            // Auth commands
-            auth_configure_sync,
-            auth_register,
-            auth_login,
-            auth_logout,
-            auth_get_status,
-            auth_is_authenticated,
-            auth_get_access_token,
-            // Browser mode
-            open_in_browser,
-            stop_browser_server,
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
-}
+mod access_tokens;
+mod api;
+mod assets;
+mod bridge;
+mod data_store;
+mod diagnostics;
+mod events;
+mod guard;
+mod lan;
+mod metrics;
+mod models;
+mod secret_store;
+mod session;
+mod single_flight;
+mod throttle;
+mod token_refresh;
+mod web_server;
+mod webhooks;
+mod ws;
+mod auth;
+mod shared_auth;
+mod shared_sync;
+mod sync_proxy;
+
+use access_tokens::{AccessToken, AccessTokenInfo, AccessTokens, SharedAccessTokens, ACCESS_TOKENS_KEY};
+use auth::{AuthService, AuthResponse, AuthStatus, ProfileInfo};
+use bridge::{DesktopBridge, SharedBridge, SyncCredentials};
+use data_store::{DataStore, DesktopChange, DesktopSnapshot, SharedDataStore, DATA_STORE_FILE};
+use futures_util::future::BoxFuture;
+use events::{EventBus, ServerEvent, SharedEventBus, TAURI_EVENT};
+use secret_store::{SecretStore, SharedSecretStore};
+use serde::Serialize;
+use session::{SessionInfo, SessionManager, SessionScope, SharedSessionManager};
+use std::sync::{Arc, Mutex};
+use throttle::TokenGuessingAlert;
+use tauri::{Emitter, Manager};
+use tauri_plugin_store::StoreExt;
+use web_server::{ServerHandle, Services, WEB_SERVER_PORT};
+use webhooks::{Delivery, SharedWebhooks, Webhook, WebhookEvent, Webhooks, WEBHOOKS_KEY, WEBHOOKS_STORE_FILE};
+
+pub struct AppState {
+    auth: Arc<Mutex<AuthService>>,
+}
+
+/// Tauri event asking the desktop window to run a sync on behalf of a browser tab
+const SYNC_REQUESTED_EVENT: &str = "sync-requested";
+
+/// Tauri event warning the desktop window that a client is guessing session tokens
+const TOKEN_GUESSING_EVENT: &str = "token-guessing";
+
+/// Tauri event carrying a row written through the web API to the desktop window
+const DATA_WRITTEN_EVENT: &str = "browser-data-written";
+
+/// Gives the web server access to the auth service and the desktop window
+struct TauriBridge {
+    app: tauri::AppHandle,
+}
+
+impl TauriBridge {
+    fn auth(&self) -> Result<AuthService, String> {
+        Ok(self
+            .app
+            .state::<AppState>()
+            .auth
+            .lock()
+            .map_err(|e| format!("Failed to lock auth: {}", e))?
+            .clone())
+    }
+}
+
+impl DesktopBridge for TauriBridge {
+    fn auth_status(&self) -> BoxFuture<'_, Result<serde_json::Value, String>> {
+        Box::pin(async move {
+            let auth = self.auth()?;
+            let status = auth.get_auth_status().await;
+            serde_json::to_value(status).map_err(|e| e.to_string())
+        })
+    }
+
+    fn request_sync(&self) -> Result<(), String> {
+        self.app
+            .emit(SYNC_REQUESTED_EVENT, ())
+            .map_err(|e| format!("Failed to reach desktop window: {}", e))
+    }
+
+    fn sync_server_url(&self) -> Result<String, String> {
+        let (server_url, _, _) = self.auth()?.sync_target()?;
+        Ok(server_url)
+    }
+
+    fn sync_credentials(&self, force_refresh: bool) -> BoxFuture<'_, Result<SyncCredentials, String>> {
+        Box::pin(async move {
+            let auth = self.auth()?;
+            auth.ensure_sync_allowed()?;
+            let access_token = auth.get_valid_access_token(force_refresh).await?;
+            let (server_url, app_id, api_key) = auth.sync_target()?;
+            Ok(SyncCredentials {
+                server_url,
+                app_id,
+                api_key,
+                access_token,
+            })
+        })
+    }
+
+    fn token_guessing_detected(&self, alert: &TokenGuessingAlert) {
+        let _ = self.app.emit(TOKEN_GUESSING_EVENT, alert);
+    }
+
+    fn data_written(&self, change: &DesktopChange) {
+        if let Err(e) = self.app.emit(DATA_WRITTEN_EVENT, change) {
+            eprintln!("Failed to send {} {} to the desktop window: {}", change.table, change.id, e);
+        }
+    }
+}
+
+struct WebServerState {
+    handle: Mutex<Option<ServerHandle>>,
+    /// HTTPS server bound to the LAN interface while sharing with other devices
+    lan_handle: Mutex<Option<ServerHandle>>,
+    /// Unix socket server for local tools and scripts
+    #[cfg(unix)]
+    socket_handle: Mutex<Option<web_server::SocketServerHandle>>,
+    /// Preferred port; the server falls back to an ephemeral port if it is taken
+    port: u16,
+}
+
+// Auth commands
+#[tauri::command]
+async fn auth_configure_sync(
+    server_url: Option<String>,
+    app_id: Option<String>,
+    api_key: Option<String>,
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.configure_sync(server_url, app_id, api_key).await
+}
+
+#[tauri::command]
+async fn auth_register(
+    username: String,
+    email: String,
+    password: String,
+    state: tauri::State<'_, AppState>,
+) -> Result<AuthResponse, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.register(username, email, password).await
+}
+
+#[tauri::command]
+async fn auth_login(
+    email: String,
+    password: String,
+    state: tauri::State<'_, AppState>,
+    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
+    events: tauri::State<'_, SharedEventBus>,
+) -> Result<AuthResponse, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    let response = auth.login(email.clone(), password).await?;
+    let status = auth.get_auth_status().await;
+    auth_status_holder.update(shared_auth::SharedAuthStatus {
+        is_authenticated: status.is_authenticated,
+        user_id: status.user_id,
+        username: None,
+        email: Some(email),
+        apps: status.apps,
+        is_admin: status.is_admin,
+        server_url: status.server_url,
+    });
+    events.publish(ServerEvent::AuthChanged {
+        is_authenticated: status.is_authenticated,
+    });
+    Ok(response)
+}
+
+#[tauri::command]
+async fn auth_logout(
+    state: tauri::State<'_, AppState>,
+    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
+    events: tauri::State<'_, SharedEventBus>,
+) -> Result<(), String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.logout().await?;
+    auth_status_holder.clear();
+    events.publish(ServerEvent::AuthChanged {
+        is_authenticated: false,
+    });
+    Ok(())
+}
+
+#[tauri::command]
+async fn auth_get_status(
+    state: tauri::State<'_, AppState>,
+    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
+) -> Result<AuthStatus, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    let status = auth.get_auth_status().await;
+    auth_status_holder.update(shared_auth::SharedAuthStatus {
+        is_authenticated: status.is_authenticated,
+        user_id: status.user_id.clone(),
+        username: None,
+        email: None,
+        apps: status.apps.clone(),
+        is_admin: status.is_admin,
+        server_url: status.server_url.clone(),
+    });
+    Ok(status)
+}
+
+#[tauri::command]
+fn auth_list_profiles(
+    state: tauri::State<'_, AppState>,
+) -> Result<Vec<ProfileInfo>, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.list_profiles()
+}
+
+/// Switch to the profile named `profile`, creating it if needed, and return its auth status.
+/// Sync stays blocked until `auth_confirm_sync_profile` if the local data belongs to another profile.
+#[tauri::command]
+async fn auth_switch_profile(
+    profile: String,
+    state: tauri::State<'_, AppState>,
+    auth_status_holder: tauri::State<'_, shared_auth::SharedAuthStatusHolder>,
+    events: tauri::State<'_, SharedEventBus>,
+) -> Result<AuthStatus, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.switch_profile(&profile)?;
+    let status = auth.get_auth_status().await;
+    if status.is_authenticated {
+        auth_status_holder.update(shared_auth::SharedAuthStatus {
+            is_authenticated: true,
+            user_id: status.user_id.clone(),
+            username: None,
+            email: auth.profile_email(),
+            apps: status.apps.clone(),
+            is_admin: status.is_admin,
+            server_url: status.server_url.clone(),
+        });
+    } else {
+        auth_status_holder.clear();
+    }
+    events.publish(ServerEvent::AuthChanged {
+        is_authenticated: status.is_authenticated,
+    });
+    Ok(status)
+}
+
+#[tauri::command]
+async fn auth_is_authenticated(
+    state: tauri::State<'_, AppState>,
+) -> Result<bool, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    Ok(auth.is_authenticated().await)
+}
+
+#[tauri::command]
+async fn auth_get_access_token(
+    state: tauri::State<'_, AppState>,
+) -> Result<String, String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.get_access_token().await
+}
+
+/// Fail while the local data still belongs to the profile used before a switch
+#[tauri::command]
+fn auth_ensure_sync_allowed(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.ensure_sync_allowed()
+}
+
+/// Sync the local data with the active profile, after the user confirmed it belongs there
+#[tauri::command]
+fn auth_confirm_sync_profile(
+    state: tauri::State<'_, AppState>,
+) -> Result<(), String> {
+    let auth = state.auth.lock().map_err(|e| format!("Failed to lock auth: {}", e))?.clone();
+    auth.confirm_sync_profile()
+}
+
+// Sync runs in the desktop window; it reports progress so browser tabs can follow along
+#[tauri::command]
+fn notify_sync_started(events: tauri::State<SharedEventBus>) {
+    events.publish(ServerEvent::SyncStarted);
+}
+
+#[tauri::command]
+fn notify_sync_finished(success: bool, error: Option<String>, events: tauri::State<SharedEventBus>) {
+    events.publish(ServerEvent::SyncFinished { success, error });
+}
+
+// The desktop window's IndexedDB holds the data; the web server serves a copy kept up to date here
+#[tauri::command]
+fn browser_data_snapshot(snapshot: DesktopSnapshot, services: tauri::State<Services>) -> Result<(), String> {
+    let skipped = services.data_store.load_desktop_snapshot(snapshot)?;
+    if skipped > 0 {
+        eprintln!("Browser mode is missing {} rows it could not read", skipped);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+fn browser_data_changed(changes: Vec<DesktopChange>, services: tauri::State<Services>) -> Result<(), String> {
+    for event in services.data_store.apply_desktop_changes(changes)? {
+        services.events.publish(event);
+    }
+    Ok(())
+}
+
+// Browser mode commands
+#[tauri::command]
+fn open_in_browser(
+    scope: Option<SessionScope>,
+    services: tauri::State<Services>,
+    web_state: tauri::State<WebServerState>,
+) -> Result<String, String> {
+    let mut handle_guard = web_state.handle.lock().map_err(|e| e.to_string())?;
+
+    // A server that died since it was started is replaced rather than reused
+    let server_port = match handle_guard.as_ref().filter(|handle| handle.is_running()) {
+        Some(handle) => handle.addr.port(),
+        None => {
+            let handle = web_server::start_web_server(services.inner().clone(), web_state.port)?;
+            let port = handle.addr.port();
+            *handle_guard = Some(handle);
+            port
+        }
+    };
+
+    // Bootstrap tokens are single-use, so every call mints a fresh one
+    let token = services.session_manager.generate_token(scope.unwrap_or_default());
+
+    // The Vite dev server serves the page but not the API, so it is told which port the API got
+    let is_dev_mode = std::env::var("TAURI_DEV_HOST").is_ok() || std::env::var("CARGO_MANIFEST_DIR").is_ok();
+    let url = if is_dev_mode {
+        format!(
+            "http://localhost:{}?session={}&apiPort={}",
+            web_server::DEV_SERVER_PORT,
+            token,
+            server_port
+        )
+    } else {
+        format!("http://localhost:{}?session={}", server_port, token)
+    };
+
+    println!("Browser sync started on port {}", server_port);
+    Ok(url)
+}
+
+/// State of the loopback web server as shown in the desktop UI
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BrowserServerStatus {
+    running: bool,
+    /// Bound address while running
+    address: Option<String>,
+    uptime_secs: Option<u64>,
+    /// Live browser sessions, including those on the LAN server
+    session_count: usize,
+}
+
+#[tauri::command]
+fn browser_server_status(
+    services: tauri::State<Services>,
+    web_state: tauri::State<WebServerState>,
+) -> Result<BrowserServerStatus, String> {
+    let handle_guard = web_state.handle.lock().map_err(|e| e.to_string())?;
+    let running = handle_guard.as_ref().filter(|handle| handle.is_running());
+    Ok(BrowserServerStatus {
+        running: running.is_some(),
+        address: running.map(|handle| handle.addr.to_string()),
+        uptime_secs: running.map(|handle| handle.started_at.elapsed().as_secs()),
+        session_count: services.session_manager.list_sessions().len(),
+    })
+}
+
+/// Stop the loopback web server and wait until its port is released.
+/// Browser sessions are signed out unless LAN sharing still needs them.
+#[tauri::command]
+async fn stop_browser_server(
+    services: tauri::State<'_, Services>,
+    web_state: tauri::State<'_, WebServerState>,
+) -> Result<(), String> {
+    let stopped = stop_server(&web_state.handle).await;
+
+    // Sessions are signed out even if the server is slow to stop
+    let lan_running = web_state
+        .lan_handle
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .is_some_and(|handle| handle.is_running());
+    if !lan_running {
+        services.session_manager.invalidate();
+    }
+    stopped
+}
+
+/// Stop the server in `slot`. One that does not stop in time is put back,
+/// so it is still reported as running and can be stopped again.
+async fn stop_server(slot: &Mutex<Option<ServerHandle>>) -> Result<(), String> {
+    let handle = slot.lock().map_err(|e| e.to_string())?.take();
+    let Some(handle) = handle else {
+        return Ok(());
+    };
+    let stopped = web_server::stop_web_server(&handle).await;
+    if stopped.is_err() {
+        // Unless a new server was started in the meantime
+        slot.lock().map_err(|e| e.to_string())?.get_or_insert(handle);
+    }
+    stopped
+}
+
+/// Stop the loopback web server and start it again on the same port.
+/// Sessions survive, so open tabs reconnect once the server is back.
+#[tauri::command]
+async fn restart_browser_server(
+    services: tauri::State<'_, Services>,
+    web_state: tauri::State<'_, WebServerState>,
+) -> Result<BrowserServerStatus, String> {
+    let port = web_state
+        .handle
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .map_or(web_state.port, |handle| handle.addr.port());
+    stop_server(&web_state.handle).await?;
+
+    let mut handle_guard = web_state.handle.lock().map_err(|e| e.to_string())?;
+    if handle_guard.is_none() {
+        *handle_guard = Some(web_server::start_web_server(services.inner().clone(), port)?);
+    }
+    drop(handle_guard);
+    browser_server_status(services, web_state)
+}
+
+#[tauri::command]
+fn list_browser_sessions(session_manager: tauri::State<SharedSessionManager>) -> Vec<SessionInfo> {
+    session_manager.list_sessions()
+}
+
+#[tauri::command]
+fn revoke_browser_session(
+    session_id: String,
+    session_manager: tauri::State<SharedSessionManager>,
+) -> Result<(), String> {
+    if !session_manager.revoke(&session_id) {
+        return Err(format!("Session {} not found", session_id));
+    }
+    Ok(())
+}
+
+/// A freshly created personal access token. `token` is only ever shown here.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedAccessToken {
+    token: String,
+    info: AccessTokenInfo,
+}
+
+/// Write the hashed access tokens to the auth store
+fn save_access_tokens(app_handle: &tauri::AppHandle, access_tokens: &AccessTokens) -> Result<(), String> {
+    let store = app_handle
+        .store(auth::STORE_FILE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    store.set(ACCESS_TOKENS_KEY, serde_json::json!(access_tokens.records()));
+    store.save().map_err(|e| format!("Failed to save store: {}", e))
+}
+
+/// Read the hashed access tokens from the auth store
+fn load_access_tokens(app_handle: &tauri::AppHandle) -> Result<Vec<AccessToken>, String> {
+    let store = app_handle
+        .store(auth::STORE_FILE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    match store.get(ACCESS_TOKENS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| format!("Failed to parse access tokens: {}", e)),
+        None => Ok(Vec::new()),
+    }
+}
+
+#[tauri::command]
+fn create_access_token(
+    name: String,
+    scope: Option<SessionScope>,
+    app_handle: tauri::AppHandle,
+    access_tokens: tauri::State<SharedAccessTokens>,
+) -> Result<CreatedAccessToken, String> {
+    let (token, info) = access_tokens.create(&name, scope.unwrap_or(SessionScope::ReadOnly))?;
+    if let Err(e) = save_access_tokens(&app_handle, &access_tokens) {
+        access_tokens.revoke(&info.id);
+        return Err(e);
+    }
+    Ok(CreatedAccessToken { token, info })
+}
+
+#[tauri::command]
+fn list_access_tokens(access_tokens: tauri::State<SharedAccessTokens>) -> Vec<AccessTokenInfo> {
+    access_tokens.list()
+}
+
+#[tauri::command]
+fn revoke_access_token(
+    token_id: String,
+    app_handle: tauri::AppHandle,
+    access_tokens: tauri::State<SharedAccessTokens>,
+) -> Result<(), String> {
+    if !access_tokens.revoke(&token_id) {
+        return Err(format!("Access token {} not found", token_id));
+    }
+    save_access_tokens(&app_handle, &access_tokens)
+}
+
+/// Write the webhook registry to its store; the secrets are in the secret store
+fn save_webhooks(app_handle: &tauri::AppHandle, webhooks: &[Webhook]) -> Result<(), String> {
+    let store = app_handle
+        .store(WEBHOOKS_STORE_FILE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    store.set(WEBHOOKS_KEY, serde_json::json!(webhooks));
+    store.save().map_err(|e| format!("Failed to save store: {}", e))
+}
+
+/// Read the webhook registry from its store and the secrets from the secret store,
+/// moving secrets still in the registry file into the secret store
+fn load_webhooks(app_handle: &tauri::AppHandle, secrets: &dyn SecretStore) -> Result<Vec<Webhook>, String> {
+    let store = app_handle
+        .store(WEBHOOKS_STORE_FILE)
+        .map_err(|e| format!("Failed to access store: {}", e))?;
+    let stored: Vec<Webhook> = match store.get(WEBHOOKS_KEY) {
+        Some(value) => serde_json::from_value(value).map_err(|e| format!("Failed to parse webhooks: {}", e))?,
+        None => Vec::new(),
+    };
+
+    let mut hooks = Vec::with_capacity(stored.len());
+    let mut migrated = false;
+    for mut hook in stored {
+        let key = webhooks::secret_key(&hook.id);
+        if hook.secret.is_empty() {
+            match secrets.get_secret(&key)? {
+                Some(secret) => hook.secret = secret,
+                None => {
+                    eprintln!("Skipping webhook {}: its secret is missing", hook.id);
+                    continue;
+                }
+            }
+        } else {
+            secrets.set_secret(&key, &hook.secret)?;
+            migrated = true;
+        }
+        hooks.push(hook);
+    }
+    if migrated {
+        secrets.save()?;
+        let records: Vec<Webhook> = hooks.iter().map(Webhook::without_secret).collect();
+        save_webhooks(app_handle, &records)?;
+    }
+    Ok(hooks)
+}
+
+#[tauri::command]
+fn create_webhook(
+    url: String,
+    events: Vec<WebhookEvent>,
+    app_handle: tauri::AppHandle,
+    webhooks: tauri::State<SharedWebhooks>,
+    secrets: tauri::State<SharedSecretStore>,
+) -> Result<Webhook, String> {
+    let hook = webhooks.create(&url, events)?;
+    let key = webhooks::secret_key(&hook.id);
+    let saved = secrets
+        .set_secret(&key, &hook.secret)
+        .and_then(|_| secrets.save())
+        .and_then(|_| save_webhooks(&app_handle, &webhooks.records()));
+    if let Err(e) = saved {
+        webhooks.delete(&hook.id);
+        let _ = secrets.delete(&key).and_then(|_| secrets.save());
+        return Err(e);
+    }
+    Ok(hook)
+}
+
+#[tauri::command]
+fn list_webhooks(webhooks: tauri::State<SharedWebhooks>) -> Vec<Webhook> {
+    webhooks.list()
+}
+
+#[tauri::command]
+fn delete_webhook(
+    webhook_id: String,
+    app_handle: tauri::AppHandle,
+    webhooks: tauri::State<SharedWebhooks>,
+    secrets: tauri::State<SharedSecretStore>,
+) -> Result<(), String> {
+    if !webhooks.delete(&webhook_id) {
+        return Err(format!("Webhook {} not found", webhook_id));
+    }
+    save_webhooks(&app_handle, &webhooks.records())?;
+    secrets.delete(&webhooks::secret_key(&webhook_id))?;
+    secrets.save()
+}
+
+#[tauri::command]
+fn list_webhook_deliveries(webhooks: tauri::State<SharedWebhooks>) -> Vec<Delivery> {
+    webhooks.deliveries()
+}
+
+/// Everything the desktop UI needs to pair another device on the LAN
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LanPairing {
+    /// Session URL; the server only pairs it over the certificate pinned in `fp`
+    url: String,
+    /// Certificate fingerprint to compare against the browser's warning page
+    fingerprint: String,
+    /// QR code of `url` as an SVG document
+    qr_svg: String,
+}
+
+#[tauri::command]
+fn start_lan_sharing(
+    scope: Option<SessionScope>,
+    services: tauri::State<Services>,
+    web_state: tauri::State<WebServerState>,
+) -> Result<LanPairing, String> {
+    let mut handle_guard = web_state.lan_handle.lock().map_err(|e| e.to_string())?;
+
+    let handle = match handle_guard.take().filter(|handle| handle.is_running()) {
+        Some(handle) => handle,
+        None => web_server::start_lan_server(services.inner().clone(), web_state.port)?,
+    };
+    let handle = handle_guard.insert(handle);
+    let fingerprint = handle
+        .tls_fingerprint
+        .clone()
+        .ok_or("LAN server is not using TLS")?;
+
+    let token = services.session_manager.generate_token(scope.unwrap_or_default());
+    let url = format!(
+        "https://{}/?session={}&fp={}",
+        handle.addr, token, fingerprint
+    );
+
+    println!("LAN sharing started on {}", handle.addr);
+    Ok(LanPairing {
+        qr_svg: lan::qr_svg(&url)?,
+        fingerprint: lan::display_fingerprint(&fingerprint),
+        url,
+    })
+}
+
+#[tauri::command]
+async fn stop_lan_sharing(web_state: tauri::State<'_, WebServerState>) -> Result<(), String> {
+    stop_server(&web_state.lan_handle).await
+}
+
+/// Serve the API on an owner-only Unix socket in the app data dir and
+/// return its path. Scripts connect with e.g. `curl --unix-socket <path>`.
+#[tauri::command]
+fn start_local_socket(
+    app_handle: tauri::AppHandle,
+    services: tauri::State<Services>,
+    web_state: tauri::State<WebServerState>,
+) -> Result<String, String> {
+    #[cfg(unix)]
+    {
+        let mut handle_guard = web_state.socket_handle.lock().map_err(|e| e.to_string())?;
+        if let Some(handle) = handle_guard.as_ref().filter(|handle| handle.is_running()) {
+            return Ok(handle.path.display().to_string());
+        }
+
+        let path = app_handle
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to resolve app data dir: {}", e))?
+            .join(web_server::SOCKET_DIR)
+            .join(web_server::SOCKET_FILE);
+        let handle = web_server::start_socket_server(path, services.inner().clone())?;
+        let path = handle.path.display().to_string();
+        *handle_guard = Some(handle);
+        Ok(path)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (app_handle, services, web_state);
+        Err("Unix sockets are not supported on this platform".to_string())
+    }
+}
+
+/// Stop the Unix socket server. One that does not stop in time is put back,
+/// like `stop_server` does for the TCP servers.
+#[tauri::command]
+async fn stop_local_socket(web_state: tauri::State<'_, WebServerState>) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        let slot = &web_state.socket_handle;
+        let handle = slot.lock().map_err(|e| e.to_string())?.take();
+        let Some(handle) = handle else {
+            return Ok(());
+        };
+        let stopped = web_server::stop_socket_server(&handle).await;
+        if stopped.is_err() {
+            slot.lock().map_err(|e| e.to_string())?.get_or_insert(handle);
+        }
+        stopped
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = web_state;
+        Ok(())
+    }
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_store::Builder::new().build())
+        .setup(|app| {
+            let _ = dotenvy::dotenv();
+
+            let sync_server_url = std::env::var("SYNC_SERVER_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string());
+            let sync_center_app_id = std::env::var("SYNC_CENTER_APP_ID")
+                .unwrap_or_else(|_| "money-insight".to_string());
+            let sync_center_api_key = std::env::var("SYNC_CENTER_API_KEY")
+                .unwrap_or_else(|_| "your_api_key_here".to_string());
+
+            // Initialize auth service, keeping credentials where SECRET_STORE says
+            let secrets = secret_store::from_env(app.handle())?;
+            let auth = Arc::new(Mutex::new(AuthService::new(
+                sync_server_url,
+                sync_center_app_id,
+                sync_center_api_key,
+                secrets.clone(),
+            )));
+
+            // Refresh the access token before it expires instead of when it is next used
+            tauri::async_runtime::spawn(token_refresh::run(app.handle().clone(), auth.clone()));
+
+            let app_state = AppState { auth };
+            app.handle().manage(app_state);
+
+            // Initialize session manager
+            let session_manager: SharedSessionManager = Arc::new(SessionManager::new());
+            app.handle().manage(session_manager.clone());
+
+            // Load personal access tokens for scripts using the local API
+            let access_tokens: SharedAccessTokens =
+                Arc::new(AccessTokens::new(load_access_tokens(app.handle())?));
+            app.handle().manage(access_tokens.clone());
+
+            // Initialize data store served to browser mode
+            let data_path = app.path().app_data_dir()?.join(DATA_STORE_FILE);
+            let data_store: SharedDataStore = Arc::new(DataStore::open(data_path)?);
+
+            // Initialize event bus and mirror its events into the desktop window
+            let events: SharedEventBus = Arc::new(EventBus::new());
+            let mut events_rx = events.subscribe();
+            let app_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                use tokio::sync::broadcast::error::RecvError;
+                loop {
+                    match events_rx.recv().await {
+                        Ok(event) => {
+                            let _ = app_handle.emit(TAURI_EVENT, &event);
+                        }
+                        Err(RecvError::Lagged(_)) => continue,
+                        Err(RecvError::Closed) => break,
+                    }
+                }
+            });
+
+            // Deliver webhooks for data changes made through the web server
+            let webhooks: SharedWebhooks = Arc::new(Webhooks::new(load_webhooks(app.handle(), secrets.as_ref())?));
+            tauri::async_runtime::spawn(webhooks::run_dispatcher(
+                events.clone(),
+                data_store.clone(),
+                webhooks.clone(),
+            ));
+            app.handle().manage(webhooks);
+            app.handle().manage(secrets);
+            app.handle().manage(events.clone());
+
+            let bridge: SharedBridge = Arc::new(TauriBridge {
+                app: app.handle().clone(),
+            });
+
+            // Everything the embedded servers are built on
+            app.handle().manage(Services {
+                session_manager,
+                access_tokens,
+                data_store,
+                events,
+                bridge,
+            });
+
+            // Initialize web server state
+            let web_server_port = std::env::var("WEB_SERVER_PORT")
+                .ok()
+                .and_then(|p| p.parse().ok())
+                .unwrap_or(WEB_SERVER_PORT);
+            app.handle().manage(WebServerState {
+                handle: Mutex::new(None),
+                lan_handle: Mutex::new(None),
+                #[cfg(unix)]
+                socket_handle: Mutex::new(None),
+                port: web_server_port,
+            });
+
+            // Initialize shared status holders
+            app.handle().manage(shared_auth::create_auth_status_holder());
+            app.handle().manage(shared_sync::create_sync_status_holder());
+
+            println!("[MoneyInsight] Application initialized with sync support");
+
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![
+            // Auth commands
+            auth_configure_sync,
+            auth_register,
+            auth_login,
+            auth_logout,
+            auth_get_status,
+            auth_is_authenticated,
+            auth_get_access_token,
+            auth_list_profiles,
+            auth_switch_profile,
+            auth_ensure_sync_allowed,
+            auth_confirm_sync_profile,
+            // Sync progress
+            notify_sync_started,
+            notify_sync_finished,
+            // Desktop data served to browser mode
+            browser_data_snapshot,
+            browser_data_changed,
+            // Browser mode
+            open_in_browser,
+            browser_server_status,
+            stop_browser_server,
+            restart_browser_server,
+            list_browser_sessions,
+            revoke_browser_session,
+            create_access_token,
+            list_access_tokens,
+            revoke_access_token,
+            create_webhook,
+            list_webhooks,
+            delete_webhook,
+            list_webhook_deliveries,
+            start_lan_sharing,
+            stop_lan_sharing,
+            start_local_socket,
+            stop_local_socket,
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
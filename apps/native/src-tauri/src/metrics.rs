@@ -0,0 +1,221 @@
+use axum::{
+    extract::State,
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use crate::models::{Account, Budget, Debt, Transaction};
+use crate::session::SessionScope;
+use crate::web_server::{validate_token, ApiResponse, AppState};
+
+/// Environment variable that turns on `/metrics`
+const METRICS_ENV: &str = "ENABLE_METRICS";
+
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Whether `/metrics` is enabled; it is off unless `ENABLE_METRICS` is set to `1` or `true`
+pub fn enabled() -> bool {
+    std::env::var(METRICS_ENV)
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Counts live connections while held
+pub struct ConnectionGuard(Arc<AtomicUsize>);
+
+impl ConnectionGuard {
+    pub fn new(counter: Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        Self(counter)
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Prometheus text exposition of balances, budgets, debts and server internals
+pub async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if !state.metrics_enabled {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::<()>::error(format!(
+                "Metrics are disabled; set {}=1 to enable them",
+                METRICS_ENV
+            ))),
+        )
+            .into_response();
+    }
+    if let Err(e) = validate_token(&state, &headers, SessionScope::ReadOnly) {
+        return e.into_response();
+    }
+
+    let transactions: Vec<Transaction> = state.data_store.list(|_| true);
+    let accounts: Vec<Account> = state.data_store.list(|_| true);
+    let budgets: Vec<Budget> = state.data_store.list(|b: &Budget| b.status == "active");
+    let debts: Vec<Debt> = state.data_store.list(|d: &Debt| !d.is_completed);
+    let today = Local::now().date_naive();
+
+    let mut out = Metrics::default();
+
+    out.header(
+        "money_insight_account_balance",
+        "Account balance: initial balance plus income minus expenses",
+    );
+    for (account, currency, balance) in account_balances(&accounts, &transactions) {
+        out.sample(
+            "money_insight_account_balance",
+            &[("account", &account), ("currency", &currency)],
+            balance,
+        );
+    }
+
+    out.header("money_insight_budget_amount", "Budget limit per cycle");
+    for budget in &budgets {
+        out.sample(
+            "money_insight_budget_amount",
+            &budget_labels(budget),
+            budget.amount,
+        );
+    }
+    let spent: Vec<f64> = budgets
+        .iter()
+        .map(|budget| budget.spent(&transactions, today))
+        .collect();
+    out.header(
+        "money_insight_budget_spent",
+        "Spending counted against the budget in the current cycle",
+    );
+    for (budget, spent) in budgets.iter().zip(&spent) {
+        out.sample("money_insight_budget_spent", &budget_labels(budget), *spent);
+    }
+    out.header(
+        "money_insight_budget_used_ratio",
+        "Current cycle spending divided by the budget amount",
+    );
+    for (budget, spent) in budgets.iter().zip(&spent) {
+        let ratio = if budget.amount > 0.0 {
+            spent / budget.amount
+        } else {
+            0.0
+        };
+        out.sample(
+            "money_insight_budget_used_ratio",
+            &budget_labels(budget),
+            ratio,
+        );
+    }
+
+    out.header(
+        "money_insight_debt_remaining",
+        "Outstanding amount of debts that are not completed",
+    );
+    for debt in &debts {
+        let labels = [
+            ("id", debt.id.as_str()),
+            ("debt", debt.name.as_str()),
+            ("type", debt.debt_type.as_str()),
+            ("counterparty", debt.counterparty_name.as_str()),
+            ("currency", debt.currency.as_str()),
+        ];
+        out.sample(
+            "money_insight_debt_remaining",
+            &labels,
+            debt.remaining_amount,
+        );
+    }
+
+    out.header("money_insight_active_sessions", "Live browser sessions");
+    out.sample(
+        "money_insight_active_sessions",
+        &[],
+        state.session_manager.list_sessions().len() as f64,
+    );
+    out.header("money_insight_access_tokens", "Personal access tokens");
+    out.sample(
+        "money_insight_access_tokens",
+        &[],
+        state.access_tokens.list().len() as f64,
+    );
+    out.header(
+        "money_insight_sse_subscribers",
+        "Open SSE streams on this server",
+    );
+    out.sample(
+        "money_insight_sse_subscribers",
+        &[],
+        state.sse_subscribers.load(Ordering::Relaxed) as f64,
+    );
+
+    ([(header::CONTENT_TYPE, CONTENT_TYPE)], out.text).into_response()
+}
+
+/// Same as the wallet balances in the spending store: every transaction counts,
+/// including transfers, and transactions on unknown accounts get their own series
+fn account_balances(
+    accounts: &[Account],
+    transactions: &[Transaction],
+) -> Vec<(String, String, f64)> {
+    let mut balances: BTreeMap<String, (String, f64)> = accounts
+        .iter()
+        .map(|a| (a.name.clone(), (a.currency.clone(), a.initial_balance)))
+        .collect();
+    for tx in transactions {
+        let entry = balances
+            .entry(tx.account.clone())
+            .or_insert_with(|| (tx.currency.clone(), 0.0));
+        entry.1 += tx.income - tx.expense;
+    }
+    balances
+        .into_iter()
+        .map(|(account, (currency, balance))| (account, currency, balance))
+        .collect()
+}
+
+/// Names need not be unique, so the id keeps two budgets from sharing a series
+fn budget_labels(budget: &Budget) -> [(&str, &str); 3] {
+    [
+        ("id", &budget.id),
+        ("budget", &budget.name),
+        ("currency", &budget.currency),
+    ]
+}
+
+#[derive(Default)]
+struct Metrics {
+    text: String,
+}
+
+impl Metrics {
+    fn header(&mut self, name: &str, help: &str) {
+        let _ = writeln!(self.text, "# HELP {} {}", name, help);
+        let _ = writeln!(self.text, "# TYPE {} gauge", name);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.text.push_str(name);
+        if !labels.is_empty() {
+            let labels: Vec<String> = labels
+                .iter()
+                .map(|(key, value)| format!("{}=\"{}\"", key, escape(value)))
+                .collect();
+            let _ = write!(self.text, "{{{}}}", labels.join(","));
+        }
+        let _ = writeln!(self.text, " {}", value);
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,700 @@
+use chrono::{Datelike, Duration, NaiveDate, SecondsFormat, Utc};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// A row in one of the synced tables served by the web API
+pub trait Record: Clone + Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Request body accepted on create and update
+    type Input: DeserializeOwned + Send + 'static;
+
+    /// Singular name used in error messages
+    const NAME: &'static str;
+
+    fn id(&self) -> &str;
+
+    /// Build a new row from a request body
+    fn create(id: String, input: Self::Input) -> Result<Self, String>;
+
+    /// Replace editable fields, keeping identity and creation time
+    fn update(&self, input: Self::Input) -> Result<Self, String>;
+
+    /// Debt whose settled amount this row counts towards
+    fn settles_debt(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// Allowed values for `transactions.source`
+pub const TRANSACTION_SOURCES: &[&str] = &[
+    "csv_import",
+    "manual",
+    "balance_adjustment",
+    "transfer",
+    "debt_initialization",
+    "debt_settlement",
+];
+
+/// Transaction row, matching the `transactions` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transaction {
+    pub id: String,
+    pub source: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transfer_id: Option<String>,
+    pub note: String,
+    pub amount: f64,
+    pub category: String,
+    pub account: String,
+    pub currency: String,
+    pub date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event: Option<String>,
+    pub exclude_report: bool,
+    pub expense: f64,
+    pub income: f64,
+    pub year_month: String,
+    pub year: i32,
+    pub month: u32,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+/// Request body for creating or updating a transaction
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTransaction {
+    pub note: String,
+    pub amount: f64,
+    pub category: String,
+    pub account: String,
+    pub currency: String,
+    pub date: String,
+    #[serde(default)]
+    pub event: Option<String>,
+    #[serde(default)]
+    pub exclude_report: bool,
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub transfer_id: Option<String>,
+}
+
+/// Query filters for listing transactions
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionFilter {
+    pub year_month: Option<String>,
+    pub account: Option<String>,
+    pub category: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+fn default_sync_version() -> i64 {
+    1
+}
+
+/// Current time as an ISO 8601 string, same format as JS `toISOString()`
+pub fn now_iso() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+pub(crate) fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    date.get(..10)
+        .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+        .ok_or_else(|| format!("Invalid date '{}', expected YYYY-MM-DD", date))
+}
+
+impl NewTransaction {
+    fn validate(&self) -> Result<(NaiveDate, String), String> {
+        let source = self.source.clone().unwrap_or_else(|| "manual".to_string());
+        if !TRANSACTION_SOURCES.contains(&source.as_str()) {
+            return Err(format!("Invalid transaction source '{}'", source));
+        }
+        if !self.amount.is_finite() {
+            return Err("Amount must be a finite number".to_string());
+        }
+        require("Account", &self.account)?;
+        Ok((parse_date(&self.date)?, source))
+    }
+}
+
+impl Record for Transaction {
+    type Input = NewTransaction;
+    const NAME: &'static str = "Transaction";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Derives expense/income and date-bucketing fields from amount and date
+    fn create(id: String, input: NewTransaction) -> Result<Self, String> {
+        let (date, source) = input.validate()?;
+        let now = now_iso();
+        Ok(Self {
+            id,
+            source,
+            transfer_id: input.transfer_id,
+            note: input.note,
+            amount: input.amount,
+            category: input.category,
+            account: input.account,
+            currency: input.currency,
+            date: input.date,
+            event: input.event,
+            exclude_report: input.exclude_report,
+            expense: if input.amount < 0.0 {
+                input.amount.abs()
+            } else {
+                0.0
+            },
+            income: if input.amount > 0.0 {
+                input.amount
+            } else {
+                0.0
+            },
+            year_month: format!("{}-{:02}", date.year(), date.month()),
+            year: date.year(),
+            month: date.month(),
+            created_at: now.clone(),
+            updated_at: now,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    fn update(&self, input: NewTransaction) -> Result<Self, String> {
+        let mut updated = Self::create(self.id.clone(), input)?;
+        updated.created_at = self.created_at.clone();
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+}
+
+impl Transaction {
+    pub fn matches(&self, filter: &TransactionFilter) -> bool {
+        let date = self.date.get(..10).unwrap_or(&self.date);
+        filter
+            .year_month
+            .as_deref()
+            .is_none_or(|ym| self.year_month == ym)
+            && filter.account.as_deref().is_none_or(|a| self.account == a)
+            && filter
+                .category
+                .as_deref()
+                .is_none_or(|c| self.category == c)
+            && filter.start_date.as_deref().is_none_or(|s| date >= s)
+            && filter.end_date.as_deref().is_none_or(|e| date <= e)
+    }
+}
+
+fn require(field: &str, value: &str) -> Result<(), String> {
+    if value.trim().is_empty() {
+        return Err(format!("{} is required", field));
+    }
+    Ok(())
+}
+
+fn require_amount(field: &str, value: f64) -> Result<(), String> {
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("{} must be a non-negative number", field));
+    }
+    Ok(())
+}
+
+/// Account row, matching the `accounts` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    pub initial_balance: f64,
+    pub currency: String,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewAccount {
+    pub name: String,
+    #[serde(default)]
+    pub account_type: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub initial_balance: f64,
+    pub currency: String,
+}
+
+impl Record for Account {
+    type Input = NewAccount;
+    const NAME: &'static str = "Account";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn create(id: String, input: NewAccount) -> Result<Self, String> {
+        require("Account name", &input.name)?;
+        if !input.initial_balance.is_finite() {
+            return Err("Initial balance must be a finite number".to_string());
+        }
+        let now = now_iso();
+        Ok(Self {
+            id,
+            name: input.name,
+            account_type: input.account_type,
+            icon: input.icon,
+            initial_balance: input.initial_balance,
+            currency: input.currency,
+            created_at: now.clone(),
+            updated_at: now,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    fn update(&self, input: NewAccount) -> Result<Self, String> {
+        let mut updated = Self::create(self.id.clone(), input)?;
+        updated.created_at = self.created_at.clone();
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+}
+
+/// Category row, matching the `categories` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    pub id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub is_expense: bool,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewCategory {
+    pub name: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default = "default_true")]
+    pub is_expense: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Record for Category {
+    type Input = NewCategory;
+    const NAME: &'static str = "Category";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn create(id: String, input: NewCategory) -> Result<Self, String> {
+        require("Category name", &input.name)?;
+        Ok(Self {
+            id,
+            name: input.name,
+            icon: input.icon,
+            color: input.color,
+            is_expense: input.is_expense,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    fn update(&self, input: NewCategory) -> Result<Self, String> {
+        let mut updated = Self::create(self.id.clone(), input)?;
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+}
+
+/// Allowed values for `budgets.status`
+pub const BUDGET_STATUSES: &[&str] = &["active", "paused"];
+
+/// Budget row, matching the `budgets` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Budget {
+    pub id: String,
+    pub name: String,
+    pub amount: f64,
+    pub currency: String,
+    pub category_names: Vec<String>,
+    pub account_names: Vec<String>,
+    pub first_cycle_start_date: String,
+    pub status: String,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewBudget {
+    pub name: String,
+    pub amount: f64,
+    pub currency: String,
+    #[serde(default)]
+    pub category_names: Vec<String>,
+    #[serde(default)]
+    pub account_names: Vec<String>,
+    pub first_cycle_start_date: String,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+impl Record for Budget {
+    type Input = NewBudget;
+    const NAME: &'static str = "Budget";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn create(id: String, input: NewBudget) -> Result<Self, String> {
+        require("Budget name", &input.name)?;
+        require_amount("Budget amount", input.amount)?;
+        parse_date(&input.first_cycle_start_date)?;
+        let status = input.status.unwrap_or_else(|| "active".to_string());
+        if !BUDGET_STATUSES.contains(&status.as_str()) {
+            return Err(format!("Invalid budget status '{}'", status));
+        }
+        let now = now_iso();
+        Ok(Self {
+            id,
+            name: input.name,
+            amount: input.amount,
+            currency: input.currency,
+            category_names: input.category_names,
+            account_names: input.account_names,
+            first_cycle_start_date: input.first_cycle_start_date,
+            status,
+            created_at: now.clone(),
+            updated_at: now,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    fn update(&self, input: NewBudget) -> Result<Self, String> {
+        let mut updated = Self::create(self.id.clone(), input)?;
+        updated.created_at = self.created_at.clone();
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+}
+
+impl Budget {
+    /// Whether `tx` counts against this budget, matching `transactionMatchesBudget`
+    pub fn matches(&self, tx: &Transaction) -> bool {
+        !tx.exclude_report
+            && tx.amount < 0.0
+            && tx.source != "transfer"
+            && tx.source != "balance_adjustment"
+            && tx.currency == self.currency
+            && self.category_names.contains(&tx.category)
+            && (self.account_names.is_empty() || self.account_names.contains(&tx.account))
+    }
+
+    /// First and last day of the monthly cycle containing `date`. Cycles start on the
+    /// day of month of the first cycle, clamped to the length of shorter months.
+    pub fn cycle(&self, date: NaiveDate) -> Result<(NaiveDate, NaiveDate), String> {
+        let first = parse_date(&self.first_cycle_start_date)?;
+        let day = first.day();
+        let start = if date < first {
+            first
+        } else {
+            let start = clamped_date(date.year(), date.month0() as i32, day);
+            if start > date {
+                clamped_date(date.year(), date.month0() as i32 - 1, day)
+            } else {
+                start
+            }
+        };
+        let next = clamped_date(start.year(), start.month0() as i32 + 1, day);
+        Ok((start, next - Duration::days(1)))
+    }
+
+    /// Spending in the cycle containing `date`, matching `calculateBudgetUsage`
+    pub fn spent(&self, transactions: &[Transaction], date: NaiveDate) -> f64 {
+        let Ok((start, end)) = self.cycle(date) else {
+            return 0.0;
+        };
+        transactions
+            .iter()
+            .filter(|tx| self.matches(tx))
+            .filter(|tx| parse_date(&tx.date).is_ok_and(|date| date >= start && date <= end))
+            .map(|tx| tx.amount.abs())
+            .sum()
+    }
+}
+
+/// `day` of the given month, or its last day if the month is shorter.
+/// `month0` may be out of range and rolls over into neighbouring years.
+fn clamped_date(year: i32, month0: i32, day: u32) -> NaiveDate {
+    let year = year + month0.div_euclid(12);
+    let month = month0.rem_euclid(12) as u32 + 1;
+    (1..=day)
+        .rev()
+        .find_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+        .unwrap_or_default()
+}
+
+/// Allowed values for `debts.debtType`
+pub const DEBT_TYPES: &[&str] = &["payable", "receivable"];
+
+/// Debt row, matching the `debts` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Debt {
+    pub id: String,
+    pub name: String,
+    pub debt_type: String,
+    pub counterparty_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_transaction_id: Option<String>,
+    pub account_id: String,
+    pub currency: String,
+    pub principal_amount: f64,
+    pub settled_amount: f64,
+    pub remaining_amount: f64,
+    pub is_completed: bool,
+    pub originated_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+/// Request body for debts; `settledAmount` defaults to zero on create
+/// and to the stored value on update
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewDebt {
+    pub name: String,
+    pub debt_type: String,
+    pub counterparty_name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub initial_transaction_id: Option<String>,
+    pub account_id: String,
+    pub currency: String,
+    pub principal_amount: f64,
+    #[serde(default)]
+    pub settled_amount: Option<f64>,
+    pub originated_at: String,
+    #[serde(default)]
+    pub due_date: Option<String>,
+}
+
+impl Debt {
+    fn build(id: String, input: NewDebt, settled_amount: f64) -> Result<Self, String> {
+        require("Debt name", &input.name)?;
+        require("Counterparty name", &input.counterparty_name)?;
+        require("Account", &input.account_id)?;
+        if !DEBT_TYPES.contains(&input.debt_type.as_str()) {
+            return Err(format!("Invalid debt type '{}'", input.debt_type));
+        }
+        require_amount("Principal amount", input.principal_amount)?;
+        let settled_amount = input.settled_amount.unwrap_or(settled_amount);
+        require_amount("Settled amount", settled_amount)?;
+        if settled_amount > input.principal_amount {
+            return Err("Settled amount cannot exceed the principal".to_string());
+        }
+        parse_date(&input.originated_at)?;
+        if let Some(due_date) = &input.due_date {
+            parse_date(due_date)?;
+        }
+        let remaining_amount = input.principal_amount - settled_amount;
+        let now = now_iso();
+        Ok(Self {
+            id,
+            name: input.name,
+            debt_type: input.debt_type,
+            counterparty_name: input.counterparty_name,
+            description: input.description,
+            initial_transaction_id: input.initial_transaction_id,
+            account_id: input.account_id,
+            currency: input.currency,
+            principal_amount: input.principal_amount,
+            settled_amount,
+            remaining_amount,
+            is_completed: remaining_amount <= 0.0,
+            originated_at: input.originated_at,
+            due_date: input.due_date,
+            completed_at: (remaining_amount <= 0.0).then(|| now.clone()),
+            created_at: now.clone(),
+            updated_at: now,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    /// The debt with its settled and remaining amounts recomputed from its
+    /// settlements, like `recomputeDebt` in the desktop app. `None` if nothing changed.
+    pub fn settle(&self, settlements: &[&DebtSettlement]) -> Option<Self> {
+        let active: Vec<_> = settlements.iter().filter(|s| s.amount > 0.0).collect();
+        let settled_amount: f64 = active.iter().map(|s| s.amount).sum();
+        let remaining_amount = (self.principal_amount - settled_amount).max(0.0);
+        let is_completed = remaining_amount <= 0.0;
+        let completed_at = if is_completed {
+            active.iter().map(|s| s.settled_at.clone()).max()
+        } else {
+            None
+        };
+        if settled_amount == self.settled_amount
+            && remaining_amount == self.remaining_amount
+            && is_completed == self.is_completed
+            && completed_at == self.completed_at
+        {
+            return None;
+        }
+        Some(Self {
+            settled_amount,
+            remaining_amount,
+            is_completed,
+            completed_at,
+            updated_at: now_iso(),
+            sync_version: self.sync_version + 1,
+            synced_at: None,
+            ..self.clone()
+        })
+    }
+}
+
+impl Record for Debt {
+    type Input = NewDebt;
+    const NAME: &'static str = "Debt";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn create(id: String, input: NewDebt) -> Result<Self, String> {
+        Self::build(id, input, 0.0)
+    }
+
+    fn update(&self, input: NewDebt) -> Result<Self, String> {
+        let mut updated = Self::build(self.id.clone(), input, self.settled_amount)?;
+        if self.is_completed && updated.is_completed {
+            updated.completed_at = self.completed_at.clone();
+        }
+        updated.created_at = self.created_at.clone();
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+}
+
+/// Debt settlement row, matching the `debtSettlements` table in the app schema
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DebtSettlement {
+    pub id: String,
+    pub debt_id: String,
+    pub transaction_id: String,
+    pub account_id: String,
+    pub amount: f64,
+    pub settled_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+    #[serde(default = "default_sync_version")]
+    pub sync_version: i64,
+    #[serde(default)]
+    pub synced_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewDebtSettlement {
+    pub debt_id: String,
+    pub transaction_id: String,
+    pub account_id: String,
+    pub amount: f64,
+    pub settled_at: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl Record for DebtSettlement {
+    type Input = NewDebtSettlement;
+    const NAME: &'static str = "Debt settlement";
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn create(id: String, input: NewDebtSettlement) -> Result<Self, String> {
+        require("Debt", &input.debt_id)?;
+        require("Transaction", &input.transaction_id)?;
+        require("Account", &input.account_id)?;
+        require_amount("Settlement amount", input.amount)?;
+        parse_date(&input.settled_at)?;
+        let now = now_iso();
+        Ok(Self {
+            id,
+            debt_id: input.debt_id,
+            transaction_id: input.transaction_id,
+            account_id: input.account_id,
+            amount: input.amount,
+            settled_at: input.settled_at,
+            note: input.note,
+            created_at: now.clone(),
+            updated_at: now,
+            sync_version: 1,
+            synced_at: None,
+        })
+    }
+
+    fn update(&self, input: NewDebtSettlement) -> Result<Self, String> {
+        let mut updated = Self::create(self.id.clone(), input)?;
+        updated.created_at = self.created_at.clone();
+        updated.sync_version = self.sync_version + 1;
+        Ok(updated)
+    }
+
+    fn settles_debt(&self) -> Option<&str> {
+        Some(&self.debt_id)
+    }
+}
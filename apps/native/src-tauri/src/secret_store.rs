@@ -0,0 +1,526 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tauri_plugin_store::StoreExt;
+
+use crate::auth::STORE_FILE;
+
+/// Environment variable choosing where credentials are kept: `file` (the
+/// default), `keyring` or `memory`
+const BACKEND_ENV: &str = "SECRET_STORE";
+
+/// `auth.json` key of the per-install key derivation salt
+const KEY_ENCRYPTION_SALT: &str = "encryption_salt";
+
+/// Where `AuthService` keeps credentials and account details
+///
+/// Secrets are protected by the backend, encrypted on disk or held by the OS
+/// keyring; plain values such as the user ID are stored as JSON. Writes may be
+/// buffered until `save`.
+pub trait SecretStore: Send + Sync {
+    fn get_secret(&self, key: &str) -> Result<Option<String>, String>;
+    fn set_secret(&self, key: &str, secret: &str) -> Result<(), String>;
+    fn get(&self, key: &str) -> Result<Option<Value>, String>;
+    fn set(&self, key: &str, value: Value) -> Result<(), String>;
+    /// Remove a secret or plain value; missing keys are not an error
+    fn delete(&self, key: &str) -> Result<(), String>;
+    fn save(&self) -> Result<(), String>;
+}
+
+pub type SharedSecretStore = Arc<dyn SecretStore>;
+
+/// Backend selected by `SECRET_STORE`
+pub fn from_env(app: &tauri::AppHandle) -> Result<SharedSecretStore, String> {
+    match std::env::var(BACKEND_ENV).as_deref() {
+        Err(_) | Ok("file") => Ok(Arc::new(EncryptedFileStore::new(app.clone()))),
+        #[cfg(not(target_os = "android"))]
+        Ok("keyring") => Ok(Arc::new(KeyringStore::new(&app.config().identifier))),
+        Ok("memory") => Ok(Arc::new(MemoryStore::default())),
+        Ok(other) => Err(format!("Unsupported {} backend: {}", BACKEND_ENV, other)),
+    }
+}
+
+/// `auth.json` through the store plugin, with secrets encrypted by a key
+/// derived from the device identifier and a per-install salt
+pub struct EncryptedFileStore {
+    app: tauri::AppHandle,
+}
+
+impl EncryptedFileStore {
+    pub fn new(app: tauri::AppHandle) -> Self {
+        Self { app }
+    }
+
+    fn store(&self) -> Result<Arc<tauri_plugin_store::Store<tauri::Wry>>, String> {
+        self.app
+            .store(STORE_FILE)
+            .map_err(|e| format!("Failed to access store: {}", e))
+    }
+
+    /// Random salt for this install, created on first use. Every entry also
+    /// carries the salt it was encrypted with, so losing this only triggers
+    /// re-encryption.
+    fn install_salt(&self) -> Result<[u8; crypto::SALT_LEN], String> {
+        let store = self.store()?;
+        let stored: Option<[u8; crypto::SALT_LEN]> = store
+            .get(KEY_ENCRYPTION_SALT)
+            .and_then(|v| v.as_str().and_then(|s| BASE64.decode(s).ok()))
+            .and_then(|bytes| bytes.try_into().ok());
+        if let Some(salt) = stored {
+            return Ok(salt);
+        }
+        let salt = crypto::new_salt();
+        store.set(KEY_ENCRYPTION_SALT, Value::String(BASE64.encode(salt)));
+        Ok(salt)
+    }
+}
+
+impl SecretStore for EncryptedFileStore {
+    fn get_secret(&self, key: &str) -> Result<Option<String>, String> {
+        let Some(Value::String(encrypted)) = self.get(key)? else {
+            return Ok(None);
+        };
+        let salt = self.install_salt()?;
+        let opened = crypto::decrypt(&encrypted, &salt)?;
+        // Written by an older scheme; upgrade it now that the plaintext is at hand
+        if opened.stale {
+            if let Err(e) = self
+                .set_secret(key, &opened.plaintext)
+                .and_then(|_| self.save())
+            {
+                eprintln!("Failed to re-encrypt {}: {}", key, e);
+            }
+        }
+        Ok(Some(opened.plaintext))
+    }
+
+    fn set_secret(&self, key: &str, secret: &str) -> Result<(), String> {
+        let salt = self.install_salt()?;
+        self.set(key, Value::String(crypto::encrypt(secret, &salt)?))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        Ok(self.store()?.get(key))
+    }
+
+    fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        self.store()?.set(key, value);
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.store()?.delete(key);
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        self.store()?
+            .save()
+            .map_err(|e| format!("Failed to save store: {}", e))
+    }
+}
+
+/// Keeps everything in memory, for tests and sessions that should leave no trace
+#[derive(Default)]
+pub struct MemoryStore {
+    values: Mutex<HashMap<String, Value>>,
+}
+
+impl SecretStore for MemoryStore {
+    fn get_secret(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self
+            .values
+            .lock()
+            .unwrap()
+            .get(key)
+            .and_then(|v| v.as_str().map(|s| s.to_string())))
+    }
+
+    fn set_secret(&self, key: &str, secret: &str) -> Result<(), String> {
+        self.set(key, Value::String(secret.to_string()))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        Ok(self.values.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        self.values.lock().unwrap().insert(key.to_string(), value);
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.values.lock().unwrap().remove(key);
+        Ok(())
+    }
+
+    fn save(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The OS credential store: Secret Service on Linux, Keychain on macOS and iOS,
+/// Credential Manager on Windows. Plain values are kept there too, as JSON text.
+#[cfg(not(target_os = "android"))]
+pub struct KeyringStore {
+    service: String,
+}
+
+#[cfg(not(target_os = "android"))]
+impl KeyringStore {
+    /// Entries are filed under `service`, usually the app identifier
+    pub fn new(service: &str) -> Self {
+        Self {
+            service: service.to_string(),
+        }
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(&self.service, key)
+            .map_err(|e| format!("Failed to open keyring entry {}: {}", key, e))
+    }
+}
+
+#[cfg(not(target_os = "android"))]
+impl SecretStore for KeyringStore {
+    fn get_secret(&self, key: &str) -> Result<Option<String>, String> {
+        match self.entry(key)?.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("Failed to read {} from keyring: {}", key, e)),
+        }
+    }
+
+    fn set_secret(&self, key: &str, secret: &str) -> Result<(), String> {
+        self.entry(key)?
+            .set_password(secret)
+            .map_err(|e| format!("Failed to write {} to keyring: {}", key, e))
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Value>, String> {
+        self.get_secret(key)?
+            .map(|json| {
+                serde_json::from_str(&json)
+                    .map_err(|e| format!("Failed to parse {} from keyring: {}", key, e))
+            })
+            .transpose()
+    }
+
+    fn set(&self, key: &str, value: Value) -> Result<(), String> {
+        self.set_secret(key, &value.to_string())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        match self.entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("Failed to delete {} from keyring: {}", key, e)),
+        }
+    }
+
+    // Every write goes straight to the keyring
+    fn save(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+mod crypto {
+    use argon2::password_hash::SaltString;
+    use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use chacha20poly1305::{
+        aead::{Aead, KeyInit, Payload},
+        ChaCha20Poly1305,
+    };
+
+    /// Envelope layout: version, Argon2id m/t/p costs (little-endian u32),
+    /// salt, nonce, ciphertext. Bump when the layout or cipher changes.
+    const VERSION: u8 = 1;
+    pub const SALT_LEN: usize = 16;
+    const NONCE_LEN: usize = 12;
+    const HEADER_LEN: usize = 1 + 3 * 4 + SALT_LEN;
+    /// Upper bound on the memory cost read from an envelope (1 GiB), so a
+    /// corrupt entry cannot make key derivation exhaust memory
+    const MAX_M_COST: u32 = 1 << 20;
+
+    /// Argon2id costs, kept in every envelope so they can be raised later
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct KdfParams {
+        pub m_cost: u32,
+        pub t_cost: u32,
+        pub p_cost: u32,
+    }
+
+    impl KdfParams {
+        pub const CURRENT: KdfParams = KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        };
+    }
+
+    /// A decrypted entry, and whether it should be written again with the
+    /// current envelope, parameters and install salt
+    pub struct Opened {
+        pub plaintext: String,
+        pub stale: bool,
+    }
+
+    pub fn new_salt() -> [u8; SALT_LEN] {
+        rand::random()
+    }
+
+    pub fn encrypt(plaintext: &str, salt: &[u8; SALT_LEN]) -> Result<String, String> {
+        seal(
+            &get_device_identifier()?,
+            salt,
+            KdfParams::CURRENT,
+            plaintext,
+        )
+    }
+
+    /// Decrypt an entry written by any version of this store
+    pub fn decrypt(encrypted: &str, install_salt: &[u8; SALT_LEN]) -> Result<Opened, String> {
+        open(&get_device_identifier()?, install_salt, encrypted)
+    }
+
+    fn get_device_identifier() -> Result<String, String> {
+        #[cfg(target_os = "android")]
+        {
+            let android_id = std::env::var("ANDROID_DATA")
+                .or_else(|_| std::env::var("EXTERNAL_STORAGE"))
+                .unwrap_or_else(|_| "money-insight-android-device".to_string());
+            use sha2::{Digest, Sha256};
+            let mut hasher = Sha256::new();
+            hasher.update(android_id.as_bytes());
+            hasher.update(b"money-insight-unique-salt");
+            let result = hasher.finalize();
+            Ok(hex::encode(result))
+        }
+
+        #[cfg(not(target_os = "android"))]
+        {
+            machine_uid::get().map_err(|e| format!("Failed to get machine ID: {}", e))
+        }
+    }
+
+    fn derive_key(device_id: &str, salt: &[u8], kdf: KdfParams) -> Result<[u8; 32], String> {
+        let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+            .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(device_id.as_bytes(), salt, &mut key)
+            .map_err(|e| format!("Failed to derive encryption key: {}", e))?;
+        Ok(key)
+    }
+
+    fn seal(
+        device_id: &str,
+        salt: &[u8; SALT_LEN],
+        kdf: KdfParams,
+        plaintext: &str,
+    ) -> Result<String, String> {
+        let mut envelope = Vec::with_capacity(HEADER_LEN + NONCE_LEN + plaintext.len() + 16);
+        envelope.push(VERSION);
+        for cost in [kdf.m_cost, kdf.t_cost, kdf.p_cost] {
+            envelope.extend_from_slice(&cost.to_le_bytes());
+        }
+        envelope.extend_from_slice(salt);
+
+        let key_bytes = derive_key(device_id, salt, kdf)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&key_bytes)
+            .map_err(|e| format!("Failed to create cipher: {}", e))?;
+        let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+        let nonce = chacha20poly1305::Nonce::from(nonce_bytes);
+        // The header is authenticated so its parameters cannot be swapped out
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &envelope,
+                },
+            )
+            .map_err(|e| format!("Encryption failed: {}", e))?;
+        envelope.extend_from_slice(&nonce_bytes);
+        envelope.extend_from_slice(&ciphertext);
+        Ok(BASE64.encode(&envelope))
+    }
+
+    fn open(
+        device_id: &str,
+        install_salt: &[u8; SALT_LEN],
+        encrypted: &str,
+    ) -> Result<Opened, String> {
+        let bytes = BASE64
+            .decode(encrypted)
+            .map_err(|e| format!("Failed to decode base64: {}", e))?;
+        match open_envelope(device_id, &bytes) {
+            Ok((plaintext, kdf, salt)) => Ok(Opened {
+                plaintext,
+                stale: kdf != KdfParams::CURRENT || &salt != install_salt,
+            }),
+            // Legacy entries have no header; a random nonce can still start
+            // with the version byte, so fall back whenever the envelope fails
+            Err(e) => open_legacy(device_id, &bytes)
+                .map(|plaintext| Opened {
+                    plaintext,
+                    stale: true,
+                })
+                .map_err(|_| e),
+        }
+    }
+
+    fn open_envelope(
+        device_id: &str,
+        bytes: &[u8],
+    ) -> Result<(String, KdfParams, [u8; SALT_LEN]), String> {
+        if bytes.len() < HEADER_LEN + NONCE_LEN {
+            return Err("Encrypted data too short".to_string());
+        }
+        if bytes[0] != VERSION {
+            return Err(format!("Unsupported encryption version: {}", bytes[0]));
+        }
+        let (header, rest) = bytes.split_at(HEADER_LEN);
+        let cost = |i: usize| u32::from_le_bytes(header[1 + 4 * i..5 + 4 * i].try_into().unwrap());
+        let kdf = KdfParams {
+            m_cost: cost(0),
+            t_cost: cost(1),
+            p_cost: cost(2),
+        };
+        if kdf.m_cost > MAX_M_COST {
+            return Err("Key derivation memory cost too high".to_string());
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&header[HEADER_LEN - SALT_LEN..]);
+        let (nonce_bytes, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let key_bytes = derive_key(device_id, &salt, kdf)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&key_bytes)
+            .map_err(|e| format!("Failed to create cipher: {}", e))?;
+        let mut nonce_array = [0u8; NONCE_LEN];
+        nonce_array.copy_from_slice(nonce_bytes);
+        let nonce = chacha20poly1305::Nonce::from(nonce_array);
+        let plaintext_bytes = cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|e| format!("Decryption failed: {}", e))?;
+        let plaintext = String::from_utf8(plaintext_bytes)
+            .map_err(|e| format!("Failed to convert decrypted data to string: {}", e))?;
+        Ok((plaintext, kdf, salt))
+    }
+
+    /// Key of the pre-envelope scheme: a fixed salt, and the first 32 bytes of
+    /// the PHC-encoded hash rather than the raw hash. Only used to read old entries.
+    fn derive_legacy_key(device_id: &str) -> Result<[u8; 32], String> {
+        let app_salt = b"money-insight-auth-v1";
+        let combined = format!("{}{}", device_id, String::from_utf8_lossy(app_salt));
+        let salt = SaltString::from_b64("bW9uZXlpbnNpZ2h0c2FsdDEyMzQ1")
+            .map_err(|e| format!("Failed to create salt: {}", e))?;
+        let argon2 = Argon2::default();
+        let password_hash = argon2
+            .hash_password(combined.as_bytes(), &salt)
+            .map_err(|e| format!("Failed to hash password: {}", e))?;
+        let hash_str = password_hash.hash.ok_or("No hash generated")?;
+        let hash_bytes = hash_str.as_bytes();
+        if hash_bytes.len() < 32 {
+            return Err("Hash too short".to_string());
+        }
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&hash_bytes[..32]);
+        Ok(key)
+    }
+
+    /// Nonce followed by ciphertext, as written before the envelope existed
+    fn open_legacy(device_id: &str, bytes: &[u8]) -> Result<String, String> {
+        let key_bytes = derive_legacy_key(device_id)?;
+        let cipher = ChaCha20Poly1305::new_from_slice(&key_bytes)
+            .map_err(|e| format!("Failed to create cipher: {}", e))?;
+        if bytes.len() < NONCE_LEN {
+            return Err("Encrypted data too short".to_string());
+        }
+        let (nonce_bytes, ciphertext) = bytes.split_at(NONCE_LEN);
+        let mut nonce_array = [0u8; NONCE_LEN];
+        nonce_array.copy_from_slice(nonce_bytes);
+        let nonce = chacha20poly1305::Nonce::from(nonce_array);
+        let plaintext_bytes = cipher
+            .decrypt(&nonce, ciphertext)
+            .map_err(|e| format!("Decryption failed: {}", e))?;
+        String::from_utf8(plaintext_bytes)
+            .map_err(|e| format!("Failed to convert decrypted data to string: {}", e))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        const DEVICE: &str = "test-device";
+
+        /// Cheap enough for tests, and different from the current costs
+        const FAST: KdfParams = KdfParams {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        };
+
+        fn seal_legacy(plaintext: &str) -> String {
+            let cipher =
+                ChaCha20Poly1305::new_from_slice(&derive_legacy_key(DEVICE).unwrap()).unwrap();
+            let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+            let nonce = chacha20poly1305::Nonce::from(nonce_bytes);
+            let mut bytes = nonce_bytes.to_vec();
+            bytes.extend(cipher.encrypt(&nonce, plaintext.as_bytes()).unwrap());
+            BASE64.encode(bytes)
+        }
+
+        #[test]
+        fn envelope_round_trips() {
+            let salt = new_salt();
+            let sealed = seal(DEVICE, &salt, KdfParams::CURRENT, "secret").unwrap();
+            let opened = open(DEVICE, &salt, &sealed).unwrap();
+            assert_eq!(opened.plaintext, "secret");
+            assert!(!opened.stale);
+        }
+
+        #[test]
+        fn old_params_or_salt_are_stale() {
+            let salt = new_salt();
+            let sealed = seal(DEVICE, &salt, FAST, "secret").unwrap();
+            let opened = open(DEVICE, &salt, &sealed).unwrap();
+            assert_eq!(opened.plaintext, "secret");
+            assert!(opened.stale);
+
+            let sealed = seal(DEVICE, &salt, KdfParams::CURRENT, "secret").unwrap();
+            assert!(open(DEVICE, &new_salt(), &sealed).unwrap().stale);
+        }
+
+        #[test]
+        fn legacy_entries_open_and_are_stale() {
+            let opened = open(DEVICE, &new_salt(), &seal_legacy("secret")).unwrap();
+            assert_eq!(opened.plaintext, "secret");
+            assert!(opened.stale);
+        }
+
+        #[test]
+        fn tampered_header_is_rejected() {
+            let salt = new_salt();
+            let mut bytes = BASE64
+                .decode(seal(DEVICE, &salt, FAST, "secret").unwrap())
+                .unwrap();
+            bytes[2] ^= 1;
+            assert!(open(DEVICE, &salt, &BASE64.encode(bytes)).is_err());
+        }
+
+        #[test]
+        fn other_device_cannot_decrypt() {
+            let salt = new_salt();
+            let sealed = seal(DEVICE, &salt, FAST, "secret").unwrap();
+            assert!(open("other-device", &salt, &sealed).is_err());
+        }
+    }
+}
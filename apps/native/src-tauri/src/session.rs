@@ -1,60 +1,281 @@
-use rand::Rng;
-use std::sync::{Arc, Mutex};
-
-/// Session manager for browser authentication
-pub struct SessionManager {
-    token: Mutex<Option<String>>,
-}
-
-impl SessionManager {
-    pub fn new() -> Self {
-        Self {
-            token: Mutex::new(None),
-        }
-    }
-
-    /// Generate a 256-bit random token
-    pub fn generate_token(&self) -> String {
-        let mut rng = rand::thread_rng();
-        let bytes: [u8; 32] = rng.gen();
-        let token = hex::encode(bytes);
-
-        *self.token.lock().unwrap() = Some(token.clone());
-        token
-    }
-
-    /// Validate token using constant-time comparison
-    pub fn validate_token(&self, token: &str) -> bool {
-        let guard = self.token.lock().unwrap();
-        match &*guard {
-            Some(stored) => constant_time_eq(stored.as_bytes(), token.as_bytes()),
-            None => false,
-        }
-    }
-
-    /// Invalidate the current token
-    pub fn invalidate(&self) {
-        *self.token.lock().unwrap() = None;
-    }
-}
-
-impl Default for SessionManager {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
-/// Constant-time comparison to prevent timing attacks
-fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
-    if a.len() != b.len() {
-        return false;
-    }
-
-    let mut result = 0u8;
-    for (x, y) in a.iter().zip(b.iter()) {
-        result |= x ^ y;
-    }
-    result == 0
-}
-
-pub type SharedSessionManager = Arc<SessionManager>;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long a bootstrap token from `open_in_browser` stays usable
+const BOOTSTRAP_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Sessions unused for this long expire
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Sessions expire this long after creation regardless of activity
+pub const SESSION_MAX_AGE: Duration = Duration::from_secs(12 * 60 * 60);
+
+/// Longest user-agent string kept as a session label
+const MAX_LABEL_LEN: usize = 200;
+
+/// Tokens of ended sessions remembered, oldest forgotten first
+const MAX_RETIRED: usize = 256;
+
+/// What a browser session may do, ordered from least to most privileged
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SessionScope {
+    /// View data and reports only
+    ReadOnly,
+    /// Create, edit and delete data
+    #[default]
+    ReadWrite,
+    /// Everything, including managing other browser sessions
+    Admin,
+}
+
+impl SessionScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ReadOnly => "read-only",
+            Self::ReadWrite => "read-write",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+struct PendingBootstrap {
+    token: String,
+    scope: SessionScope,
+    created_at: SystemTime,
+}
+
+struct Session {
+    id: String,
+    token: String,
+    scope: SessionScope,
+    label: Option<String>,
+    created_at: SystemTime,
+    last_seen: SystemTime,
+}
+
+/// Browser session as reported to the desktop UI (timestamps in unix ms)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionInfo {
+    pub id: String,
+    pub scope: SessionScope,
+    pub label: Option<String>,
+    pub created_at: u64,
+    pub last_seen_at: u64,
+    pub expires_at: u64,
+}
+
+/// Session manager for browser authentication
+///
+/// `open_in_browser` hands out a one-time bootstrap token in the URL. The
+/// browser exchanges it for a session token, which is only ever sent back
+/// as a cookie or bearer header. Each exchange creates an independent
+/// session, so several tabs can be signed in at once.
+pub struct SessionManager {
+    bootstrap_tokens: Mutex<Vec<PendingBootstrap>>,
+    sessions: Mutex<Vec<Session>>,
+    /// Tokens of sessions that expired, were revoked or were invalidated, so a
+    /// tab still holding one is not mistaken for someone guessing tokens
+    retired: Mutex<VecDeque<String>>,
+    idle_timeout: Duration,
+    max_age: Duration,
+}
+
+impl SessionManager {
+    pub fn new() -> Self {
+        Self::with_expiry(SESSION_IDLE_TIMEOUT, SESSION_MAX_AGE)
+    }
+
+    pub fn with_expiry(idle_timeout: Duration, max_age: Duration) -> Self {
+        Self {
+            bootstrap_tokens: Mutex::new(Vec::new()),
+            sessions: Mutex::new(Vec::new()),
+            retired: Mutex::new(VecDeque::new()),
+            idle_timeout,
+            max_age,
+        }
+    }
+
+    /// Absolute session lifetime, used for the cookie Max-Age
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Generate a one-time bootstrap token for the browser URL. The session
+    /// it is exchanged for is limited to `scope`.
+    pub fn generate_token(&self, scope: SessionScope) -> String {
+        let token = random_hex(32);
+        let mut pending = self.bootstrap_tokens.lock().unwrap();
+        pending.retain(|b| age(b.created_at) < BOOTSTRAP_TTL);
+        pending.push(PendingBootstrap {
+            token: token.clone(),
+            scope,
+            created_at: SystemTime::now(),
+        });
+        token
+    }
+
+    /// Consume a bootstrap token and open a new session labelled with `label`
+    /// (usually the user agent). Returns the session token, or `None` if the
+    /// bootstrap token is wrong, expired or was already used.
+    pub fn exchange_bootstrap_token(
+        &self,
+        bootstrap_token: &str,
+        label: Option<String>,
+    ) -> Option<String> {
+        let scope = {
+            let mut pending = self.bootstrap_tokens.lock().unwrap();
+            pending.retain(|b| age(b.created_at) < BOOTSTRAP_TTL);
+            let index = find_token(pending.iter().map(|b| b.token.as_str()), bootstrap_token)?;
+            pending.remove(index).scope
+        };
+
+        let token = random_hex(32);
+        let now = SystemTime::now();
+        let label = label.map(|l| l.chars().take(MAX_LABEL_LEN).collect());
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.push(Session {
+            id: random_hex(8),
+            token: token.clone(),
+            scope,
+            label,
+            created_at: now,
+            last_seen: now,
+        });
+        Some(token)
+    }
+
+    /// Validate a session token and mark the session as active.
+    /// Returns the session's scope, or `None` if the token is not valid.
+    pub fn validate_token(&self, token: &str) -> Option<SessionScope> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune_expired(&mut sessions);
+        let index = find_token(sessions.iter().map(|s| s.token.as_str()), token)?;
+        sessions[index].last_seen = SystemTime::now();
+        Some(sessions[index].scope)
+    }
+
+    /// Like `validate_token`, but without marking the session as active, for
+    /// checks the server makes on its own such as before pushing an event
+    pub fn peek_token(&self, token: &str) -> Option<SessionScope> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune_expired(&mut sessions);
+        let index = find_token(sessions.iter().map(|s| s.token.as_str()), token)?;
+        Some(sessions[index].scope)
+    }
+
+    /// Active sessions, oldest first
+    pub fn list_sessions(&self) -> Vec<SessionInfo> {
+        let mut sessions = self.sessions.lock().unwrap();
+        self.prune_expired(&mut sessions);
+        sessions
+            .iter()
+            .map(|s| SessionInfo {
+                id: s.id.clone(),
+                scope: s.scope,
+                label: s.label.clone(),
+                created_at: unix_ms(s.created_at),
+                last_seen_at: unix_ms(s.last_seen),
+                expires_at: unix_ms(
+                    (s.created_at + self.max_age).min(s.last_seen + self.idle_timeout),
+                ),
+            })
+            .collect()
+    }
+
+    /// Revoke one session by id. Returns `false` if no such session exists.
+    pub fn revoke(&self, session_id: &str) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(index) = sessions.iter().position(|s| s.id == session_id) else {
+            return false;
+        };
+        let session = sessions.remove(index);
+        self.retire([session]);
+        true
+    }
+
+    /// Invalidate all bootstrap tokens and sessions
+    pub fn invalidate(&self) {
+        self.bootstrap_tokens.lock().unwrap().clear();
+        let mut sessions = self.sessions.lock().unwrap();
+        self.retire(sessions.drain(..));
+    }
+
+    /// Whether `token` belonged to a session that has since ended
+    pub fn is_retired(&self, token: &str) -> bool {
+        let retired = self.retired.lock().unwrap();
+        find_token(retired.iter().map(String::as_str), token).is_some()
+    }
+
+    fn prune_expired(&self, sessions: &mut Vec<Session>) {
+        let (live, expired) = std::mem::take(sessions).into_iter().partition(|s| {
+            age(s.created_at) < self.max_age && age(s.last_seen) < self.idle_timeout
+        });
+        *sessions = live;
+        self.retire(expired);
+    }
+
+    fn retire(&self, ended: impl IntoIterator<Item = Session>) {
+        let mut retired = self.retired.lock().unwrap();
+        retired.extend(ended.into_iter().map(|s| s.token));
+        while retired.len() > MAX_RETIRED {
+            retired.pop_front();
+        }
+    }
+}
+
+impl Default for SessionManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub(crate) fn random_hex(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    let bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+    hex::encode(bytes)
+}
+
+fn age(since: SystemTime) -> Duration {
+    since.elapsed().unwrap_or_default()
+}
+
+pub(crate) fn unix_ms(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Index of `candidate` in `tokens`. Compares against every token so the
+/// time taken does not depend on which one matched.
+pub(crate) fn find_token<'a>(
+    tokens: impl Iterator<Item = &'a str>,
+    candidate: &str,
+) -> Option<usize> {
+    let mut found = None;
+    for (index, token) in tokens.enumerate() {
+        if constant_time_eq(token.as_bytes(), candidate.as_bytes()) {
+            found = Some(index);
+        }
+    }
+    found
+}
+
+/// Constant-time comparison to prevent timing attacks
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut result = 0u8;
+    for (x, y) in a.iter().zip(b.iter()) {
+        result |= x ^ y;
+    }
+    result == 0
+}
+
+pub type SharedSessionManager = Arc<SessionManager>;
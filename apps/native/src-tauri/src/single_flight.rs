@@ -0,0 +1,175 @@
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+type Flight<T> = Shared<BoxFuture<'static, T>>;
+
+/// Runs at most one instance of an async operation at a time. Callers that
+/// arrive while it is running wait for it and get a clone of its result
+/// instead of starting their own.
+pub struct SingleFlight<T> {
+    in_flight: Arc<Mutex<Option<Flight<T>>>>,
+}
+
+impl<T: Clone + Send + Sync + 'static> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            in_flight: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Join the operation in flight, or start one with `start`
+    ///
+    /// The operation is driven by its own task, so it finishes even if every
+    /// caller gives up on it, and it is never run twice concurrently.
+    pub async fn run<F>(&self, start: impl FnOnce() -> F) -> T
+    where
+        F: Future<Output = T> + Send + 'static,
+    {
+        let flight = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.as_ref() {
+                Some(flight) => flight.clone(),
+                None => {
+                    let flight = in_flight.insert(start().boxed().shared()).clone();
+                    let (slot, driver) = (Arc::clone(&self.in_flight), flight.clone());
+                    tokio::spawn(async move {
+                        driver.clone().await;
+                        finish(&slot, &driver);
+                    });
+                    flight
+                }
+            }
+        };
+
+        let result = flight.clone().await;
+        finish(&self.in_flight, &flight);
+        result
+    }
+}
+
+/// Clear the slot once `flight` is done, so the next caller starts afresh.
+/// A newer flight stays.
+fn finish<T>(slot: &Mutex<Option<Flight<T>>>, flight: &Flight<T>) {
+    let mut in_flight = slot.lock().unwrap();
+    if in_flight
+        .as_ref()
+        .is_some_and(|current| current.ptr_eq(flight))
+    {
+        *in_flight = None;
+    }
+}
+
+impl<T: Clone + Send + Sync + 'static> Default for SingleFlight<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tokio::sync::Notify;
+
+    /// Operation that counts its runs and finishes once `release` is notified
+    #[derive(Clone, Default)]
+    struct Operation {
+        started: Arc<AtomicUsize>,
+        finished: Arc<AtomicUsize>,
+        release: Arc<Notify>,
+    }
+
+    impl Operation {
+        fn start(
+            &self,
+            result: Result<usize, String>,
+        ) -> BoxFuture<'static, Result<usize, String>> {
+            let op = self.clone();
+            async move {
+                let run = op.started.fetch_add(1, Ordering::SeqCst) + 1;
+                op.release.notified().await;
+                op.finished.fetch_add(1, Ordering::SeqCst);
+                result.map(|_| run)
+            }
+            .boxed()
+        }
+    }
+
+    /// Start `callers` concurrent runs, let them pile up, then release the
+    /// operation and collect what each caller got
+    async fn run_in_parallel(
+        flight: &Arc<SingleFlight<Result<usize, String>>>,
+        op: &Operation,
+        result: Result<usize, String>,
+        callers: usize,
+    ) -> Vec<Result<usize, String>> {
+        let tasks: Vec<_> = (0..callers)
+            .map(|_| {
+                let (flight, op, result) = (flight.clone(), op.clone(), result.clone());
+                tokio::spawn(async move { flight.run(|| op.start(result)).await })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        op.release.notify_one();
+        let mut results = Vec::new();
+        for task in tasks {
+            results.push(task.await.unwrap());
+        }
+        results
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_run() {
+        let (flight, op) = (Arc::new(SingleFlight::new()), Operation::default());
+
+        let results = run_in_parallel(&flight, &op, Ok(0), 8).await;
+
+        assert!(results.iter().all(|r| *r == Ok(1)), "{:?}", results);
+        assert_eq!(op.started.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn concurrent_callers_share_a_failure() {
+        let (flight, op) = (Arc::new(SingleFlight::new()), Operation::default());
+
+        let results = run_in_parallel(&flight, &op, Err("refused".to_string()), 8).await;
+
+        assert!(
+            results
+                .iter()
+                .all(|r| r.as_ref().err().map(String::as_str) == Some("refused")),
+            "{:?}",
+            results
+        );
+        assert_eq!(op.started.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn abandoned_run_still_completes() {
+        let (flight, op) = (Arc::new(SingleFlight::new()), Operation::default());
+
+        let abandoned =
+            tokio::time::timeout(Duration::from_millis(10), flight.run(|| op.start(Ok(0)))).await;
+        assert!(abandoned.is_err());
+        op.release.notify_one();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(op.finished.load(Ordering::SeqCst), 1);
+
+        // The finished run was cleared, so the next callers start a new one
+        let results = run_in_parallel(&flight, &op, Ok(0), 4).await;
+        assert!(results.iter().all(|r| *r == Ok(2)), "{:?}", results);
+    }
+
+    #[tokio::test]
+    async fn finished_run_is_not_reused() {
+        let (flight, op) = (Arc::new(SingleFlight::new()), Operation::default());
+
+        run_in_parallel(&flight, &op, Ok(0), 4).await;
+        let results = run_in_parallel(&flight, &op, Ok(0), 4).await;
+
+        assert!(results.iter().all(|r| *r == Ok(2)), "{:?}", results);
+        assert_eq!(op.started.load(Ordering::SeqCst), 2);
+    }
+}
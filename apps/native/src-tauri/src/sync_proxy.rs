@@ -0,0 +1,165 @@
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Path, Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+
+use crate::bridge::SyncCredentials;
+use crate::session::SessionScope;
+use crate::web_server::{validate_token, ApiResponse, AppState};
+
+/// Only sync endpoints are forwarded. Auth endpoints return tokens, which
+/// must never reach the browser.
+const ALLOWED_PREFIX: &str = "api/v1/sync/";
+
+/// Largest request body forwarded upstream
+const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+/// Request headers passed through to the sync server
+const FORWARDED_HEADERS: [header::HeaderName; 2] = [header::CONTENT_TYPE, header::ACCEPT];
+
+fn proxy_error(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(ApiResponse::<()>::error(message))).into_response()
+}
+
+/// Forward `/api/sync/<path>` to `<server>/<path>` on the configured sync server
+///
+/// The desktop app injects its access token and app credentials, so browser
+/// tabs sync without ever holding the JWT or refresh token. Problems with those
+/// credentials are never reported as 401, which would count against the tab's
+/// session in the failure throttle.
+pub async fn forward(
+    State(state): State<AppState>,
+    Path(path): Path<String>,
+    request: Request,
+) -> Response {
+    let required = if request.method() == Method::GET {
+        SessionScope::ReadOnly
+    } else {
+        SessionScope::ReadWrite
+    };
+    if let Err(e) = validate_token(&state, request.headers(), required) {
+        return e.into_response();
+    }
+    if !path.starts_with(ALLOWED_PREFIX) || path.split('/').any(|segment| segment == "..") {
+        return proxy_error(StatusCode::NOT_FOUND, format!("Cannot proxy /{}", path));
+    }
+
+    let (parts, body) = request.into_parts();
+    let body = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(body) => body,
+        Err(e) => return proxy_error(StatusCode::PAYLOAD_TOO_LARGE, e.to_string()),
+    };
+    let path_and_query = match parts.uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+
+    let credentials = match state.bridge.sync_credentials(false).await {
+        Ok(credentials) => credentials,
+        Err(e) => return proxy_error(StatusCode::SERVICE_UNAVAILABLE, e),
+    };
+    let upstream = send(
+        &state,
+        &credentials,
+        &parts.method,
+        &parts.headers,
+        &path_and_query,
+        body.clone(),
+    )
+    .await;
+
+    // The server may reject a token that has not reached its `exp` yet, e.g. after a
+    // password change elsewhere. Refresh once and retry before giving up.
+    let upstream = match upstream {
+        Ok(response) if response.status() == reqwest::StatusCode::UNAUTHORIZED => {
+            match state.bridge.sync_credentials(true).await {
+                Ok(credentials) => {
+                    send(
+                        &state,
+                        &credentials,
+                        &parts.method,
+                        &parts.headers,
+                        &path_and_query,
+                        body,
+                    )
+                    .await
+                }
+                Err(_) => Ok(response),
+            }
+        }
+        other => other,
+    };
+
+    match upstream {
+        Ok(response) if response.status() == reqwest::StatusCode::UNAUTHORIZED => proxy_error(
+            StatusCode::BAD_GATEWAY,
+            "Sync server rejected the desktop app's credentials; sign in again in the desktop app",
+        ),
+        Ok(response) => into_response(response).await,
+        Err(e) => proxy_error(
+            StatusCode::BAD_GATEWAY,
+            format!("Sync server unreachable: {}", e),
+        ),
+    }
+}
+
+async fn send(
+    state: &AppState,
+    credentials: &SyncCredentials,
+    method: &Method,
+    headers: &HeaderMap,
+    path_and_query: &str,
+    body: Bytes,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let url = format!(
+        "{}/{}",
+        credentials.server_url.trim_end_matches('/'),
+        path_and_query
+    );
+    let method =
+        reqwest::Method::from_bytes(method.as_str().as_bytes()).unwrap_or(reqwest::Method::GET);
+
+    let mut request = state
+        .http_client
+        .request(method, url)
+        .bearer_auth(&credentials.access_token)
+        .header("X-App-Id", &credentials.app_id)
+        .header("X-API-Key", &credentials.api_key)
+        .body(body);
+    for name in FORWARDED_HEADERS {
+        if let Some(value) = headers.get(&name).and_then(|v| v.to_str().ok()) {
+            request = request.header(name.as_str(), value);
+        }
+    }
+    request.send().await
+}
+
+async fn into_response(upstream: reqwest::Response) -> Response {
+    let status =
+        StatusCode::from_u16(upstream.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+    let content_type = upstream
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| HeaderValue::from_bytes(v.as_bytes()).ok());
+    let body = match upstream.bytes().await {
+        Ok(body) => body,
+        Err(e) => {
+            return proxy_error(
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to read sync server response: {}", e),
+            )
+        }
+    };
+
+    let mut response = Response::new(Body::from(body));
+    *response.status_mut() = status;
+    if let Some(content_type) = content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type);
+    }
+    response
+}
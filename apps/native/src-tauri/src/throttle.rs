@@ -0,0 +1,154 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::web_server::{session_token, ApiResponse, AppState};
+
+/// Failed attempts a client gets before it is locked out
+const FREE_ATTEMPTS: u32 = 5;
+
+/// First lockout; doubles with every further failure
+const BASE_LOCKOUT: Duration = Duration::from_secs(1);
+
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Failures from one client after which every session is invalidated,
+/// in case a guess already succeeded
+const INVALIDATE_AFTER: u32 = 20;
+
+/// A client's failures are forgotten after this long without a new one
+const FAILURE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+struct ClientFailures {
+    count: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Sent to the desktop window when a client gets locked out
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenGuessingAlert {
+    pub client: IpAddr,
+    pub failures: u32,
+    pub locked_for_secs: u64,
+    pub sessions_invalidated: bool,
+}
+
+/// Counts failed authentication attempts per client address
+///
+/// Successful requests do not reset the count: on loopback every client
+/// shares 127.0.0.1, so a legitimate tab must not wipe an attacker's record.
+pub struct FailureThrottle {
+    clients: Mutex<HashMap<IpAddr, ClientFailures>>,
+}
+
+impl FailureThrottle {
+    pub fn new() -> Self {
+        Self {
+            clients: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Remaining lockout for `client`, if any
+    pub fn locked_for(&self, client: IpAddr) -> Option<Duration> {
+        let clients = self.clients.lock().unwrap();
+        let locked_until = clients.get(&client)?.locked_until?;
+        locked_until
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Record a failed attempt. Returns an alert when it locks the client out.
+    pub fn record_failure(&self, client: IpAddr) -> Option<TokenGuessingAlert> {
+        let now = Instant::now();
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|_, c| now.duration_since(c.last_failure) < FAILURE_WINDOW);
+
+        let failures = clients.entry(client).or_insert(ClientFailures {
+            count: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        failures.count += 1;
+        failures.last_failure = now;
+        if failures.count < FREE_ATTEMPTS {
+            return None;
+        }
+
+        let doublings = (failures.count - FREE_ATTEMPTS).min(31);
+        let lockout = BASE_LOCKOUT.saturating_mul(1 << doublings).min(MAX_LOCKOUT);
+        failures.locked_until = Some(now + lockout);
+        Some(TokenGuessingAlert {
+            client,
+            failures: failures.count,
+            locked_for_secs: lockout.as_secs(),
+            sessions_invalidated: failures.count == INVALIDATE_AFTER,
+        })
+    }
+}
+
+impl Default for FailureThrottle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Middleware refusing locked-out clients and counting 401 responses as failures.
+/// A token from a session that has since ended is a stale tab rather than a
+/// guess; every loopback tab shares one address, so counting it would lock out the rest.
+pub async fn throttle_failures(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(client) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0.ip())
+    else {
+        return next.run(request).await;
+    };
+
+    if let Some(remaining) = state.throttle.locked_for(client) {
+        let seconds = remaining.as_secs().max(1);
+        return (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, seconds.to_string())],
+            Json(ApiResponse::<()>::error(
+                "Too many failed attempts, try again later",
+            )),
+        )
+            .into_response();
+    }
+
+    let token = session_token(request.headers()).map(str::to_string);
+    let response = next.run(request).await;
+    let stale = || {
+        token
+            .as_deref()
+            .is_some_and(|token| state.session_manager.is_retired(token))
+    };
+    if response.status() == StatusCode::UNAUTHORIZED && !stale() {
+        if let Some(alert) = state.throttle.record_failure(client) {
+            eprintln!(
+                "Locked out {} for {}s after {} failed attempts",
+                alert.client, alert.locked_for_secs, alert.failures
+            );
+            if alert.sessions_invalidated {
+                state.session_manager.invalidate();
+            }
+            state.bridge.token_guessing_detected(&alert);
+        }
+    }
+    response
+}
@@ -0,0 +1,126 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::Emitter;
+
+use crate::auth::{self, AuthService, RefreshError};
+
+/// Tauri event sent after the access token was refreshed in the background
+pub const TOKEN_REFRESHED_EVENT: &str = "token-refreshed";
+
+/// Tauri event sent when the refresh token was rejected and the user has to sign in again
+pub const TOKEN_REFRESH_FAILED_EVENT: &str = "token-refresh-failed";
+
+/// Environment variable overriding how long before expiry the token is refreshed
+const MARGIN_ENV: &str = "TOKEN_REFRESH_MARGIN_SECS";
+
+const DEFAULT_MARGIN: Duration = Duration::from_secs(5 * 60);
+
+/// Longest sleep between checks, so sign-ins, sign-outs and refreshes done
+/// by other callers are picked up
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// First retry delay after a network failure; doubled on every further failure
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Shortest wait after a refresh, so a server issuing tokens that already
+/// expire within the margin is not asked for new ones in a tight loop
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenRefreshed {
+    /// Expiry of the new access token in unix seconds
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenRefreshFailed {
+    pub error: String,
+}
+
+/// How long before expiry to refresh, from `TOKEN_REFRESH_MARGIN_SECS` or 5 minutes
+fn margin() -> Duration {
+    std::env::var(MARGIN_ENV)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MARGIN)
+}
+
+/// Refresh the stored access token `margin` before it expires, for as long as the app runs
+///
+/// Network failures and server errors are retried with doubling delays. A
+/// refresh the sync server rejects is reported once and not retried until the
+/// user signs in again.
+pub async fn run(app: tauri::AppHandle, auth: Arc<Mutex<AuthService>>) {
+    let margin = margin();
+    let mut retry_delay = RETRY_DELAY;
+    // Access token whose refresh was rejected
+    let mut rejected: Option<String> = None;
+
+    loop {
+        let Ok(service) = auth.lock().map(|auth| auth.clone()) else {
+            eprintln!("Token refresh stopped: auth lock is poisoned");
+            return;
+        };
+        // Signed out
+        let Ok(token) = service.get_access_token().await else {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            continue;
+        };
+        if rejected.as_ref() == Some(&token) {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            continue;
+        }
+
+        let remaining = match (auth::token_expires_at(&token), auth::unix_now()) {
+            (Ok(expires_at), Ok(now)) => {
+                Duration::from_secs(expires_at.saturating_sub(now).max(0) as u64)
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Cannot schedule token refresh: {}", e);
+                tokio::time::sleep(CHECK_INTERVAL).await;
+                continue;
+            }
+        };
+        if remaining > margin {
+            tokio::time::sleep((remaining - margin).min(CHECK_INTERVAL)).await;
+            continue;
+        }
+
+        match refresh(&service).await {
+            Ok(expires_at) => {
+                retry_delay = RETRY_DELAY;
+                let _ = app.emit(TOKEN_REFRESHED_EVENT, TokenRefreshed { expires_at });
+                tokio::time::sleep(MIN_REFRESH_INTERVAL).await;
+            }
+            Err(RefreshError::Unavailable(e)) => {
+                eprintln!(
+                    "Token refresh failed, retrying in {}s: {}",
+                    retry_delay.as_secs(),
+                    e
+                );
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+            }
+            Err(RefreshError::Rejected(e)) => {
+                eprintln!("Token refresh rejected: {}", e);
+                retry_delay = RETRY_DELAY;
+                rejected = Some(token);
+                let _ = app.emit(TOKEN_REFRESH_FAILED_EVENT, TokenRefreshFailed { error: e });
+            }
+        }
+    }
+}
+
+/// Refresh the token and return the new expiry
+async fn refresh(auth: &AuthService) -> Result<i64, RefreshError> {
+    auth.refresh_token().await?;
+    auth.get_access_token()
+        .await
+        .and_then(|token| auth::token_expires_at(&token))
+        .map_err(RefreshError::Rejected)
+}
@@ -1,240 +1,1600 @@
-use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    routing::get,
-    Json, Router,
-};
-#[cfg(not(debug_assertions))]
-use rust_embed::RustEmbed;
-use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast;
-use tower_http::cors::{Any, CorsLayer};
-
-use crate::session::SharedSessionManager;
-
-/// Port for the embedded web server
-pub const WEB_SERVER_PORT: u16 = 25096;
-
-/// Embed the dist folder at compile time (only in release mode)
-/// In debug mode, assets are served by Vite dev server, so we provide a dummy implementation
-#[cfg(not(debug_assertions))]
-#[derive(RustEmbed)]
-#[folder = "../dist"]
-struct Asset;
-
-/// Dummy Asset struct for debug/dev mode - returns None for all assets
-/// since the Vite dev server handles asset serving on port 1420
-#[cfg(debug_assertions)]
-struct Asset;
-
-#[cfg(debug_assertions)]
-impl Asset {
-    fn get(_path: &str) -> Option<rust_embed::EmbeddedFile> {
-        None // In dev mode, assets are served by Vite
-    }
-}
-
-/// Shared application state
-#[derive(Clone)]
-pub struct AppState {
-    pub session_manager: SharedSessionManager,
-    pub shutdown_tx: broadcast::Sender<String>,
-}
-
-/// API response wrapper
-#[derive(Serialize)]
-pub struct ApiResponse<T> {
-    pub success: bool,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub data: Option<T>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub error: Option<String>,
-}
-
-impl<T> ApiResponse<T> {
-    pub fn success(data: T) -> Self {
-        Self {
-            success: true,
-            data: Some(data),
-            error: None,
-        }
-    }
-
-    pub fn error(message: impl Into<String>) -> Self {
-        Self {
-            success: false,
-            data: None,
-            error: Some(message.into()),
-        }
-    }
-}
-
-/// Token query parameter for authentication
-#[derive(Deserialize)]
-pub struct TokenQuery {
-    pub token: String,
-}
-
-/// Server handle for controlling the web server
-pub struct ServerHandle {
-    pub token: String,
-    pub shutdown_tx: broadcast::Sender<String>,
-}
-
-/// Start the embedded web server
-pub fn start_web_server(session_manager: SharedSessionManager) -> ServerHandle {
-    let token = session_manager.generate_token();
-    let (shutdown_tx, _) = broadcast::channel::<String>(1);
-
-    let state = AppState {
-        session_manager,
-        shutdown_tx: shutdown_tx.clone(),
-    };
-
-    let shutdown_tx_clone = shutdown_tx.clone();
-
-    // Spawn the server in a background thread
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(async {
-            let cors = CorsLayer::new()
-                .allow_origin(Any)
-                .allow_methods(Any)
-                .allow_headers(Any);
-
-            let app = Router::new()
-                // Health check
-                .route("/api/health", get(health_check))
-                // SSE for shutdown notification
-                .route("/api/events", get(sse_handler))
-                // Static files from embedded dist
-                .fallback(get(serve_asset))
-                .layer(cors)
-                .with_state(state);
-
-            let listener = tokio::net::TcpListener::bind(format!("127.0.0.1:{}", WEB_SERVER_PORT))
-                .await
-                .expect("Failed to bind to port");
-
-            println!("Web server started on http://127.0.0.1:{}", WEB_SERVER_PORT);
-
-            let mut shutdown_rx = shutdown_tx_clone.subscribe();
-
-            axum::serve(listener, app)
-                .with_graceful_shutdown(async move {
-                    let _ = shutdown_rx.recv().await;
-                    println!("Web server shutting down...");
-                })
-                .await
-                .expect("Failed to run web server");
-        });
-    });
-
-    ServerHandle { token, shutdown_tx }
-}
-
-/// Send shutdown signal to the web server
-pub fn stop_web_server(handle: &ServerHandle) {
-    let _ = handle.shutdown_tx.send("shutdown".to_string());
-}
-
-// Helper to validate token
-fn validate_token(
-    state: &AppState,
-    query: &TokenQuery,
-) -> Result<(), (StatusCode, Json<ApiResponse<()>>)> {
-    if !state.session_manager.validate_token(&query.token) {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ApiResponse::error("Invalid session token")),
-        ));
-    }
-    Ok(())
-}
-
-// === API Handlers ===
-
-async fn health_check() -> Json<ApiResponse<&'static str>> {
-    Json(ApiResponse::success("OK"))
-}
-
-// SSE handler for shutdown notifications
-async fn sse_handler(
-    State(state): State<AppState>,
-    Query(query): Query<TokenQuery>,
-) -> Result<
-    axum::response::Sse<
-        impl futures_util::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>,
-    >,
-    (StatusCode, Json<ApiResponse<()>>),
-> {
-    use axum::response::sse::{Event, Sse};
-    use futures_util::stream;
-    use std::time::Duration;
-
-    validate_token(&state, &query)?;
-
-    let shutdown_rx = state.shutdown_tx.subscribe();
-
-    let stream = stream::unfold(
-        (shutdown_rx, false),
-        |(mut rx, sent_connected)| async move {
-            if !sent_connected {
-                return Some((
-                    Ok(Event::default()
-                        .event("connected")
-                        .data("Connected to server")),
-                    (rx, true),
-                ));
-            }
-
-            tokio::select! {
-                _ = tokio::time::sleep(Duration::from_secs(30)) => {
-                    Some((Ok(Event::default().event("ping").data("keepalive")), (rx, true)))
-                }
-                result = rx.recv() => {
-                    match result {
-                        Ok(_) => Some((Ok(Event::default().event("shutdown").data("Server is shutting down")), (rx, true))),
-                        Err(_) => None,
-                    }
-                }
-            }
-        },
-    );
-
-    Ok(Sse::new(stream))
-}
-
-// Serve static assets from embedded dist folder
-async fn serve_asset(uri: axum::http::Uri) -> Result<axum::response::Response, StatusCode> {
-    use axum::body::Body;
-    use axum::http::{header, Response};
-
-    let path = uri.path().trim_start_matches('/');
-    let path = if path.is_empty() { "index.html" } else { path };
-
-    match Asset::get(path) {
-        Some(content) => {
-            let mime = mime_guess::from_path(path)
-                .first_or_octet_stream()
-                .to_string();
-
-            Response::builder()
-                .status(StatusCode::OK)
-                .header(header::CONTENT_TYPE, mime)
-                .body(Body::from(content.data.into_owned()))
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
-        }
-        None => {
-            // Try serving index.html for SPA routing
-            match Asset::get("index.html") {
-                Some(content) => Response::builder()
-                    .status(StatusCode::OK)
-                    .header(header::CONTENT_TYPE, "text/html")
-                    .body(Body::from(content.data.into_owned()))
-                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR),
-                None => Err(StatusCode::NOT_FOUND),
-            }
-        }
-    }
-}
+use axum::{
+    extract::{ConnectInfo, Path, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware,
+    response::IntoResponse,
+    routing::{any, delete, get, post},
+    Json, Router,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener};
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::AtomicUsize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, watch};
+use tokio_rustls::TlsAcceptor;
+use tower_http::add_extension::AddExtension;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+use crate::access_tokens::{SharedAccessTokens, TOKEN_PREFIX};
+use crate::api;
+use crate::assets;
+use crate::bridge::SharedBridge;
+use crate::data_store::SharedDataStore;
+use crate::diagnostics;
+use crate::events::SharedEventBus;
+use crate::guard::{self, RequestGuard};
+use crate::lan::{self, SelfSignedCert};
+use crate::metrics::{self, ConnectionGuard};
+use crate::session::{SessionInfo, SessionScope, SharedSessionManager};
+use crate::sync_proxy;
+use crate::throttle::{self, FailureThrottle};
+use crate::ws;
+
+/// Default port for the embedded web server
+pub const WEB_SERVER_PORT: u16 = 25096;
+
+/// Port of the Vite dev server that may call the API in development
+pub const DEV_SERVER_PORT: u16 = 1420;
+
+/// Cookie carrying the browser session token
+pub const SESSION_COOKIE: &str = "mi_session";
+
+/// How long `stop_web_server` waits for open connections to finish
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shared application state
+#[derive(Clone)]
+pub struct AppState {
+    pub session_manager: SharedSessionManager,
+    /// Personal access tokens for scripts
+    pub access_tokens: SharedAccessTokens,
+    pub data_store: SharedDataStore,
+    /// Data, sync and auth changes pushed to SSE subscribers
+    pub events: SharedEventBus,
+    /// Desktop-side operations exposed over the WebSocket RPC
+    pub bridge: SharedBridge,
+    /// Host and origin allowlist for this server
+    pub guard: Arc<RequestGuard>,
+    /// Failed authentication attempts per client
+    pub throttle: Arc<FailureThrottle>,
+    /// Mark cookies `Secure` when serving HTTPS
+    pub secure_cookies: bool,
+    /// Fingerprint of the certificate served over HTTPS, which pairing
+    /// links must pin to exchange their bootstrap token
+    pub tls_fingerprint: Option<String>,
+    /// Serving the owner-only Unix socket, where the file permissions
+    /// stand in for a session token
+    pub local_socket: bool,
+    /// Serve `/metrics`, see [`metrics::enabled`]
+    pub metrics_enabled: bool,
+    /// Open SSE streams, reported in the metrics
+    pub sse_subscribers: Arc<AtomicUsize>,
+    /// When the server started, for uptime reporting
+    pub started_at: Instant,
+    /// Client for requests forwarded to the sync server
+    pub http_client: reqwest::Client,
+    pub shutdown_tx: broadcast::Sender<String>,
+}
+
+/// App-wide services every server is built on
+#[derive(Clone)]
+pub struct Services {
+    pub session_manager: SharedSessionManager,
+    pub access_tokens: SharedAccessTokens,
+    pub data_store: SharedDataStore,
+    pub events: SharedEventBus,
+    pub bridge: SharedBridge,
+}
+
+impl AppState {
+    /// State for a plain HTTP server guarded by `guard`; HTTPS and Unix
+    /// socket servers override the relevant flags
+    pub fn new(services: Services, guard: RequestGuard) -> Self {
+        let (shutdown_tx, _) = broadcast::channel::<String>(1);
+        Self {
+            session_manager: services.session_manager,
+            access_tokens: services.access_tokens,
+            data_store: services.data_store,
+            events: services.events,
+            bridge: services.bridge,
+            guard: Arc::new(guard),
+            throttle: Arc::new(FailureThrottle::new()),
+            secure_cookies: false,
+            tls_fingerprint: None,
+            local_socket: false,
+            metrics_enabled: metrics::enabled(),
+            sse_subscribers: Arc::new(AtomicUsize::new(0)),
+            started_at: Instant::now(),
+            http_client: reqwest::Client::new(),
+            shutdown_tx,
+        }
+    }
+}
+
+/// API response wrapper
+#[derive(Serialize)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn success(data: T) -> Self {
+        Self {
+            success: true,
+            data: Some(data),
+            error: None,
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: None,
+            error: Some(message.into()),
+        }
+    }
+}
+
+/// Health check payload; `scope` is present when the request carries a valid session
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HealthStatus {
+    pub status: &'static str,
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<SessionScope>,
+}
+
+/// Request body for exchanging the one-time bootstrap token
+#[derive(Deserialize)]
+pub struct SessionExchange {
+    pub token: String,
+    /// Certificate fingerprint pinned in a LAN pairing link
+    #[serde(default)]
+    pub fingerprint: Option<String>,
+}
+
+/// Server handle for controlling the web server
+pub struct ServerHandle {
+    /// Address the server actually bound, which may differ from the requested port
+    pub addr: SocketAddr,
+    /// SHA-256 fingerprint of the self-signed certificate when serving HTTPS
+    pub tls_fingerprint: Option<String>,
+    pub started_at: Instant,
+    pub shutdown_tx: broadcast::Sender<String>,
+    /// Set once the server thread has exited and the port is released
+    stopped: watch::Receiver<bool>,
+}
+
+impl ServerHandle {
+    /// Whether the server thread is still serving. `false` after a shutdown
+    /// or if the server failed after starting.
+    pub fn is_running(&self) -> bool {
+        server_running(&self.stopped)
+    }
+}
+
+fn server_running(stopped: &watch::Receiver<bool>) -> bool {
+    // A dropped sender means the thread died without reporting
+    !*stopped.borrow() && stopped.has_changed().is_ok()
+}
+
+/// Wait until a server thread has exited; `false` if it did not within `SHUTDOWN_TIMEOUT`
+async fn wait_stopped(stopped: &watch::Receiver<bool>) -> bool {
+    let mut stopped = stopped.clone();
+    // An error means the thread is gone, which is just as stopped
+    let waited = tokio::time::timeout(SHUTDOWN_TIMEOUT, stopped.wait_for(|stopped| *stopped)).await;
+    waited.is_ok()
+}
+
+/// Bind `port` on `ip`, falling back to an OS-assigned port if it is taken
+fn bind_listener(ip: IpAddr, port: u16) -> Result<TcpListener, String> {
+    let listener = TcpListener::bind((ip, port)).or_else(|e| {
+        eprintln!("Port {} unavailable ({}), using an ephemeral port", port, e);
+        TcpListener::bind((ip, 0))
+    });
+    let listener = listener.map_err(|e| format!("Failed to bind web server: {}", e))?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure web server socket: {}", e))?;
+    Ok(listener)
+}
+
+/// Build the router shared by the loopback, LAN and Unix socket servers
+pub fn router(state: AppState) -> Router {
+    // Credentialed requests need explicit origins; only the Vite dev
+    // server is cross-origin, production pages are same-origin
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(state.guard.origins()))
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
+        .allow_credentials(true);
+
+    Router::new()
+        // Health check
+        .route("/api/health", get(health_check))
+        // Version, auth, sync and storage details for support scripts
+        .route("/api/diagnostics", get(diagnostics::diagnostics_handler))
+        // Bootstrap token -> session cookie exchange
+        .route("/api/session", post(exchange_session))
+        // Browser session management (admin scope)
+        .route("/api/sessions", get(list_sessions))
+        .route("/api/sessions/:id", delete(revoke_session))
+        // SSE for server events and shutdown notification
+        .route("/api/events", get(sse_handler))
+        // Bidirectional RPC with server push
+        .route("/api/ws", get(ws::ws_handler))
+        // Sync server requests, authenticated with the desktop app's token
+        .route("/api/sync/*path", any(sync_proxy::forward))
+        // Prometheus metrics (opt-in)
+        .route("/metrics", get(metrics::metrics_handler))
+        // Data API
+        .merge(api::routes())
+        // Static files from embedded dist
+        .fallback(get(assets::serve_asset))
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            throttle::throttle_failures,
+        ))
+        // Outermost, so rejected requests never reach CORS or the handlers
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            guard::guard_requests,
+        ))
+        .with_state(state)
+}
+
+/// Start the embedded web server on `port` (or a fallback port if it is in use)
+pub fn start_web_server(services: Services, port: u16) -> Result<ServerHandle, String> {
+    let listener = bind_listener(Ipv4Addr::LOCALHOST.into(), port)?;
+    spawn_server(listener, services, None)
+}
+
+/// Start an HTTPS server on the LAN interface so phones on the same network
+/// can connect. Uses a freshly generated self-signed certificate whose
+/// fingerprint is reported in the handle for pinning in the pairing URL.
+pub fn start_lan_server(services: Services, port: u16) -> Result<ServerHandle, String> {
+    let ip = lan::lan_ip()?;
+    let cert = SelfSignedCert::generate(ip)?;
+    let listener = bind_listener(ip, port)?;
+    spawn_server(listener, services, Some(cert))
+}
+
+fn spawn_server(
+    listener: TcpListener,
+    services: Services,
+    cert: Option<SelfSignedCert>,
+) -> Result<ServerHandle, String> {
+    // Build the runtime up front so failures reach the caller
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to read web server address: {}", e))?;
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to start web server runtime: {}", e))?;
+
+    let tls = cert.as_ref().map(|cert| cert.tls_acceptor()).transpose()?;
+    let tls_fingerprint = cert.map(|cert| cert.fingerprint);
+
+    let state = AppState {
+        secure_cookies: tls.is_some(),
+        tls_fingerprint: tls_fingerprint.clone(),
+        ..AppState::new(services, RequestGuard::new(addr, tls.is_some()))
+    };
+    let shutdown_tx = state.shutdown_tx.clone();
+    let shutdown_rx = shutdown_tx.subscribe();
+
+    let (stopped_tx, stopped) = watch::channel(false);
+
+    // Spawn the server in a background thread
+    std::thread::spawn(move || {
+        rt.block_on(async {
+            let app = router(state);
+
+            let listener = match tokio::net::TcpListener::from_std(listener) {
+                Ok(listener) => listener,
+                Err(e) => {
+                    eprintln!("Failed to register web server socket: {}", e);
+                    return;
+                }
+            };
+
+            if let Some(acceptor) = tls {
+                println!("Web server started on https://{}", addr);
+                serve_tls(listener, acceptor, app, shutdown_rx).await;
+                return;
+            }
+
+            println!("Web server started on http://{}", addr);
+            serve_http(listener, app, shutdown_rx).await;
+        });
+        // Dropping the runtime closes the listener, so the port is free once this is seen
+        drop(rt);
+        let _ = stopped_tx.send(true);
+    });
+
+    Ok(ServerHandle {
+        addr,
+        tls_fingerprint,
+        started_at: Instant::now(),
+        shutdown_tx,
+        stopped,
+    })
+}
+
+/// Serve `app` over plain HTTP until a shutdown message arrives. Runs on the
+/// caller's runtime, so tests can drive the router on an ephemeral port.
+pub async fn serve_http(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    mut shutdown_rx: broadcast::Receiver<String>,
+) {
+    let result = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(async move {
+        let _ = shutdown_rx.recv().await;
+        println!("Web server shutting down...");
+    })
+    .await;
+    if let Err(e) = result {
+        eprintln!("Web server stopped with error: {}", e);
+    }
+}
+
+// axum::serve only speaks plain TCP, so TLS connections are driven by hyper directly
+async fn serve_tls(
+    listener: tokio::net::TcpListener,
+    acceptor: TlsAcceptor,
+    app: Router,
+    mut shutdown_rx: broadcast::Receiver<String>,
+) {
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = shutdown_rx.recv() => break,
+        };
+
+        let acceptor = acceptor.clone();
+        let builder = builder.clone();
+        // Same extension axum::serve adds, for per-client failure tracking
+        let service = TowerToHyperService::new(AddExtension::new(app.clone(), ConnectInfo(peer)));
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            // Handshake failures are expected until the user trusts the certificate
+            let Ok(stream) = acceptor.accept(stream).await else {
+                return;
+            };
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(conn).await {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+
+    println!("Web server shutting down...");
+    graceful.shutdown().await;
+}
+
+/// Shut the web server down and wait until its thread has exited and the
+/// port is released. Open SSE and WebSocket clients are told first.
+pub async fn stop_web_server(handle: &ServerHandle) -> Result<(), String> {
+    let _ = handle.shutdown_tx.send("shutdown".to_string());
+    if wait_stopped(&handle.stopped).await {
+        return Ok(());
+    }
+    Err(format!(
+        "Web server on {} did not stop within {}s",
+        handle.addr,
+        SHUTDOWN_TIMEOUT.as_secs()
+    ))
+}
+
+/// Directory inside the app data dir holding the Unix socket
+#[cfg(unix)]
+pub const SOCKET_DIR: &str = "socket";
+
+/// File name of the Unix socket inside `SOCKET_DIR`
+#[cfg(unix)]
+pub const SOCKET_FILE: &str = "money-insight.sock";
+
+/// Handle for the Unix socket server
+#[cfg(unix)]
+pub struct SocketServerHandle {
+    pub path: PathBuf,
+    pub shutdown_tx: broadcast::Sender<String>,
+    /// Set once the server thread has exited and the socket file is gone
+    stopped: watch::Receiver<bool>,
+}
+
+#[cfg(unix)]
+impl SocketServerHandle {
+    /// Whether the server thread is still serving
+    pub fn is_running(&self) -> bool {
+        server_running(&self.stopped)
+    }
+}
+
+/// Serve the API on a Unix socket at `path` that only the current user can
+/// open. Requests need no session token: whoever can connect already has
+/// the user's file permissions.
+///
+/// The socket's directory is restricted to the user before binding, since the
+/// socket itself is created with the umask and only narrowed afterwards.
+#[cfg(unix)]
+pub fn start_socket_server(
+    path: PathBuf,
+    services: Services,
+) -> Result<SocketServerHandle, String> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let parent = path.parent().ok_or("Socket path has no parent directory")?;
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(parent)
+        .and_then(|_| std::fs::set_permissions(parent, std::fs::Permissions::from_mode(0o700)))
+        .map_err(|e| format!("Failed to create socket directory: {}", e))?;
+    if path.exists() {
+        if UnixStream::connect(&path).is_ok() {
+            return Err(format!(
+                "Another instance is already serving {}",
+                path.display()
+            ));
+        }
+        // Left behind by a previous run that did not shut down cleanly
+        std::fs::remove_file(&path).map_err(|e| format!("Failed to remove stale socket: {}", e))?;
+    }
+
+    let listener =
+        UnixListener::bind(&path).map_err(|e| format!("Failed to bind Unix socket: {}", e))?;
+    let configured = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .and_then(|_| listener.set_nonblocking(true))
+        .and_then(|_| std::fs::metadata(&path));
+    let bound = match configured {
+        Ok(metadata) => (metadata.dev(), metadata.ino()),
+        Err(e) => {
+            let _ = std::fs::remove_file(&path);
+            return Err(format!("Failed to configure Unix socket: {}", e));
+        }
+    };
+    let rt = tokio::runtime::Runtime::new()
+        .map_err(|e| format!("Failed to start web server runtime: {}", e))?;
+
+    let state = AppState {
+        local_socket: true,
+        ..AppState::new(services, RequestGuard::unix_socket())
+    };
+    let shutdown_tx = state.shutdown_tx.clone();
+    let shutdown_rx = shutdown_tx.subscribe();
+    let (stopped_tx, stopped) = watch::channel(false);
+    let socket_path = path.clone();
+    std::thread::spawn(move || {
+        rt.block_on(async {
+            match tokio::net::UnixListener::from_std(listener) {
+                Ok(listener) => {
+                    println!("Web server started on unix:{}", socket_path.display());
+                    serve_unix(listener, router(state), shutdown_rx).await;
+                }
+                Err(e) => eprintln!("Failed to register Unix socket: {}", e),
+            }
+        });
+        drop(rt);
+        // A server started since may already have bound a new socket at the same path
+        let still_ours = std::fs::metadata(&socket_path)
+            .is_ok_and(|metadata| (metadata.dev(), metadata.ino()) == bound);
+        if still_ours {
+            let _ = std::fs::remove_file(&socket_path);
+        }
+        let _ = stopped_tx.send(true);
+    });
+
+    Ok(SocketServerHandle {
+        path,
+        shutdown_tx,
+        stopped,
+    })
+}
+
+// Like serve_tls, without the handshake; there is no peer address to track failures by
+#[cfg(unix)]
+async fn serve_unix(
+    listener: tokio::net::UnixListener,
+    app: Router,
+    mut shutdown_rx: broadcast::Receiver<String>,
+) {
+    let graceful = GracefulShutdown::new();
+    let builder = auto::Builder::new(TokioExecutor::new());
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {}", e);
+                    continue;
+                }
+            },
+            _ = shutdown_rx.recv() => break,
+        };
+
+        let builder = builder.clone();
+        let service = TowerToHyperService::new(app.clone());
+        let watcher = graceful.watcher();
+        tokio::spawn(async move {
+            let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+            if let Err(e) = watcher.watch(conn).await {
+                eprintln!("Connection error: {}", e);
+            }
+        });
+    }
+
+    println!("Web server shutting down...");
+    graceful.shutdown().await;
+}
+
+/// Shut the Unix socket server down and wait until its thread has exited
+/// and the socket file is removed
+#[cfg(unix)]
+pub async fn stop_socket_server(handle: &SocketServerHandle) -> Result<(), String> {
+    let _ = handle.shutdown_tx.send("shutdown".to_string());
+    if wait_stopped(&handle.stopped).await {
+        return Ok(());
+    }
+    Err(format!(
+        "Unix socket server on {} did not stop within {}s",
+        handle.path.display(),
+        SHUTDOWN_TIMEOUT.as_secs()
+    ))
+}
+
+/// Read the session token from the session cookie or an `Authorization: Bearer` header
+pub(crate) fn session_token(headers: &HeaderMap) -> Option<&str> {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if bearer.is_some() {
+        return bearer;
+    }
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == SESSION_COOKIE).then_some(value)
+        })
+}
+
+// Helper to validate token and check the session may act at `required` scope
+pub(crate) fn validate_token(
+    state: &AppState,
+    headers: &HeaderMap,
+    required: SessionScope,
+) -> Result<SessionScope, (StatusCode, Json<ApiResponse<()>>)> {
+    authorize(state, session_token(headers).unwrap_or_default(), required)
+}
+
+/// Scope of a browser session token or personal access token, if it is valid.
+/// Only requests from the client (`touch`) keep an idle session alive.
+fn token_scope(state: &AppState, token: &str, touch: bool) -> Option<SessionScope> {
+    if state.local_socket {
+        return Some(SessionScope::Admin);
+    }
+    if token.starts_with(TOKEN_PREFIX) {
+        state.access_tokens.validate(token)
+    } else if touch {
+        state.session_manager.validate_token(token)
+    } else {
+        state.session_manager.peek_token(token)
+    }
+}
+
+/// Whether a push connection opened with `token` may still receive events.
+/// Pushing does not count as activity, so idle sessions still expire.
+pub(crate) fn still_authorized(state: &AppState, token: &str) -> bool {
+    token_scope(state, token, false).is_some()
+}
+
+/// Check `token` belongs to a live session or access token allowed to act at `required` scope
+pub(crate) fn authorize(
+    state: &AppState,
+    token: &str,
+    required: SessionScope,
+) -> Result<SessionScope, (StatusCode, Json<ApiResponse<()>>)> {
+    let scope = token_scope(state, token, true).ok_or_else(|| {
+        (
+            StatusCode::UNAUTHORIZED,
+            Json(ApiResponse::error("Invalid session token")),
+        )
+    })?;
+    if scope < required {
+        return Err((
+            StatusCode::FORBIDDEN,
+            Json(ApiResponse::error(format!(
+                "A {} session cannot perform this action",
+                scope.as_str()
+            ))),
+        ));
+    }
+    Ok(scope)
+}
+
+// === API Handlers ===
+
+// A token that is presented must be valid, so health cannot be used to test guesses unthrottled
+async fn health_check(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<HealthStatus>>, (StatusCode, Json<ApiResponse<()>>)> {
+    let scope = match session_token(&headers) {
+        Some(token) => Some(authorize(&state, token, SessionScope::ReadOnly)?),
+        None => None,
+    };
+    Ok(Json(ApiResponse::success(HealthStatus {
+        status: "OK",
+        version: diagnostics::APP_VERSION,
+        uptime_secs: state.started_at.elapsed().as_secs(),
+        scope,
+    })))
+}
+
+// Swap the one-time bootstrap token from the URL for an HttpOnly session cookie
+async fn exchange_session(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(body): Json<SessionExchange>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiResponse<()>>)> {
+    // Pages cannot read the certificate they were loaded over, so the server
+    // checks the link's pin against the one it serves before pairing
+    if let Some(served) = &state.tls_fingerprint {
+        let pinned = body.fingerprint.as_deref().map(lan::normalize_fingerprint);
+        if pinned.as_ref() != Some(served) {
+            return Err((
+                StatusCode::MISDIRECTED_REQUEST,
+                Json(ApiResponse::error(
+                    "Pairing link was issued for a different certificate",
+                )),
+            ));
+        }
+    }
+
+    let user_agent = headers
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string());
+    let token = state
+        .session_manager
+        .exchange_bootstrap_token(&body.token, user_agent)
+        .ok_or_else(|| {
+            (
+                StatusCode::UNAUTHORIZED,
+                Json(ApiResponse::error(
+                    "Invalid or already used bootstrap token",
+                )),
+            )
+        })?;
+
+    let mut cookie = format!(
+        "{}={}; HttpOnly; SameSite=Strict; Path=/; Max-Age={}",
+        SESSION_COOKIE,
+        token,
+        state.session_manager.max_age().as_secs()
+    );
+    if state.secure_cookies {
+        cookie.push_str("; Secure");
+    }
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(ApiResponse::success(())),
+    ))
+}
+
+async fn list_sessions(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<Vec<SessionInfo>>>, (StatusCode, Json<ApiResponse<()>>)> {
+    validate_token(&state, &headers, SessionScope::Admin)?;
+    Ok(Json(ApiResponse::success(
+        state.session_manager.list_sessions(),
+    )))
+}
+
+async fn revoke_session(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Json<ApiResponse<()>>, (StatusCode, Json<ApiResponse<()>>)> {
+    validate_token(&state, &headers, SessionScope::Admin)?;
+    if !state.session_manager.revoke(&id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            Json(ApiResponse::error(format!("Session {} not found", id))),
+        ));
+    }
+    Ok(Json(ApiResponse::success(())))
+}
+
+// SSE handler for server events and shutdown notifications
+async fn sse_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> Result<
+    axum::response::Sse<
+        impl futures_util::Stream<Item = Result<axum::response::sse::Event, std::convert::Infallible>>,
+    >,
+    (StatusCode, Json<ApiResponse<()>>),
+> {
+    use axum::response::sse::{Event, Sse};
+    use futures_util::{stream, StreamExt};
+    use tokio::sync::broadcast::error::RecvError;
+
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+    let token = session_token(&headers).unwrap_or_default().to_string();
+
+    let subscriber = ConnectionGuard::new(state.sse_subscribers.clone());
+    let receivers = (
+        state.shutdown_tx.subscribe(),
+        state.events.subscribe(),
+        subscriber,
+        state,
+        token,
+    );
+
+    let connected = stream::once(async {
+        Ok(Event::default()
+            .event("connected")
+            .data("Connected to server"))
+    });
+
+    // `None` state ends the stream after the shutdown notice so graceful shutdown can finish
+    let updates = stream::unfold(Some(receivers), |receivers| async move {
+        let (mut shutdown_rx, mut events_rx, subscriber, state, token) = receivers?;
+        loop {
+            let event = tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(30)) => {
+                    Event::default().event("ping").data("keepalive")
+                }
+                result = shutdown_rx.recv() => {
+                    let Ok(_) = result else {
+                        return None;
+                    };
+                    let event = Event::default().event("shutdown").data("Server is shutting down");
+                    return Some((Ok(event), None));
+                }
+                result = events_rx.recv() => match result {
+                    Ok(event) => match Event::default().event(event.name()).json_data(&event) {
+                        Ok(sse_event) => sse_event,
+                        Err(e) => {
+                            eprintln!("Failed to encode {} event: {}", event.name(), e);
+                            continue;
+                        }
+                    },
+                    // Tell the client it missed changes so it reloads everything
+                    Err(RecvError::Lagged(_)) => Event::default().event("resync").data("Events were missed"),
+                    Err(RecvError::Closed) => return None,
+                },
+            };
+            // Stop pushing data to sessions that were revoked or expired
+            if !still_authorized(&state, &token) {
+                return None;
+            }
+            return Some((
+                Ok(event),
+                Some((shutdown_rx, events_rx, subscriber, state, token)),
+            ));
+        }
+    });
+
+    Ok(Sse::new(connected.chain(updates)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access_tokens::AccessTokens;
+    use crate::bridge::{DesktopBridge, SyncCredentials};
+    use crate::data_store::{DataStore, DesktopChange};
+    use crate::events::{EventBus, ServerEvent};
+    use crate::models::Account;
+    use crate::session::SessionManager;
+    use crate::throttle::TokenGuessingAlert;
+    use futures_util::future::BoxFuture;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{json, Value};
+    use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+    /// Stands in for the desktop app, which is not running in tests
+    struct NoDesktop;
+
+    impl DesktopBridge for NoDesktop {
+        fn auth_status(&self) -> BoxFuture<'_, Result<serde_json::Value, String>> {
+            Box::pin(async { Err("No desktop app".to_string()) })
+        }
+
+        fn request_sync(&self) -> Result<(), String> {
+            Err("No desktop app".to_string())
+        }
+
+        fn sync_server_url(&self) -> Result<String, String> {
+            Err("No desktop app".to_string())
+        }
+
+        fn sync_credentials(
+            &self,
+            _force_refresh: bool,
+        ) -> BoxFuture<'_, Result<SyncCredentials, String>> {
+            Box::pin(async { Err("No desktop app".to_string()) })
+        }
+
+        fn token_guessing_detected(&self, _alert: &TokenGuessingAlert) {}
+
+        fn data_written(&self, _change: &DesktopChange) {}
+    }
+
+    fn services(session_manager: SessionManager) -> Services {
+        Services {
+            session_manager: Arc::new(session_manager),
+            access_tokens: Arc::new(AccessTokens::default()),
+            data_store: Arc::new(DataStore::in_memory()),
+            events: Arc::new(EventBus::new()),
+            bridge: Arc::new(NoDesktop),
+        }
+    }
+
+    /// The full router on an ephemeral loopback port
+    struct TestServer {
+        base: String,
+        state: AppState,
+        client: reqwest::Client,
+    }
+
+    impl TestServer {
+        async fn start(session_manager: SessionManager) -> Self {
+            Self::start_with(session_manager, |state| state).await
+        }
+
+        /// Start with the default state adjusted by `configure`
+        async fn start_with(
+            session_manager: SessionManager,
+            configure: impl FnOnce(AppState) -> AppState,
+        ) -> Self {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let state = configure(AppState::new(
+                services(session_manager),
+                RequestGuard::new(addr, false),
+            ));
+            let shutdown_rx = state.shutdown_tx.subscribe();
+            tokio::spawn(serve_http(listener, router(state.clone()), shutdown_rx));
+            Self {
+                base: format!("http://{}", addr),
+                state,
+                client: reqwest::Client::new(),
+            }
+        }
+
+        fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+            self.client
+                .request(method, format!("{}{}", self.base, path))
+        }
+
+        fn get(&self, path: &str) -> reqwest::RequestBuilder {
+            self.request(reqwest::Method::GET, path)
+        }
+
+        /// Token of a new session limited to `scope`
+        fn session(&self, scope: SessionScope) -> String {
+            let bootstrap = self.state.session_manager.generate_token(scope);
+            self.state
+                .session_manager
+                .exchange_bootstrap_token(&bootstrap, None)
+                .unwrap()
+        }
+    }
+
+    type WsClient = tokio_tungstenite::WebSocketStream<
+        tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>,
+    >;
+
+    impl TestServer {
+        /// Open the JSON-RPC WebSocket as the session `token` belongs to
+        async fn connect_ws(&self, token: &str) -> WsClient {
+            let url = format!("{}/api/ws", self.base.replacen("http", "ws", 1));
+            let mut request = url.into_client_request().unwrap();
+            request.headers_mut().insert(
+                header::AUTHORIZATION,
+                format!("Bearer {}", token).parse().unwrap(),
+            );
+            tokio_tungstenite::connect_async(request).await.unwrap().0
+        }
+    }
+
+    /// Next JSON message on the socket, or `None` once it is closed
+    async fn next_message(ws: &mut WsClient) -> Option<Value> {
+        loop {
+            let message = tokio::time::timeout(Duration::from_secs(5), ws.next())
+                .await
+                .expect("timed out waiting for a message");
+            match message {
+                Some(Ok(Message::Text(text))) => return Some(serde_json::from_str(&text).unwrap()),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+                Some(Ok(_)) => continue,
+            }
+        }
+    }
+
+    /// Send `text` and wait for the response, skipping pushed notifications
+    async fn send_rpc(ws: &mut WsClient, text: String) -> Value {
+        ws.send(Message::Text(text)).await.unwrap();
+        loop {
+            let message = next_message(ws).await.expect("socket closed");
+            if message.get("id").is_some() {
+                return message;
+            }
+        }
+    }
+
+    async fn rpc(ws: &mut WsClient, method: &str, params: Value) -> Value {
+        let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        send_rpc(ws, request.to_string()).await
+    }
+
+    /// Read the socket until a notification of `method` arrives
+    async fn next_notification(ws: &mut WsClient, method: &str) -> Value {
+        loop {
+            let message = next_message(ws).await.expect("socket closed");
+            if message["method"] == method {
+                return message;
+            }
+        }
+    }
+
+    /// Read the SSE stream until an event named `name` arrives
+    async fn next_event(response: &mut reqwest::Response, name: &str) -> String {
+        let marker = format!("event: {}\n", name);
+        let mut received = String::new();
+        while !received.contains(&marker) {
+            let chunk = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+                .await
+                .expect("timed out waiting for event")
+                .unwrap()
+                .expect("stream ended");
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        received
+    }
+
+    #[tokio::test]
+    async fn health_reports_scope_and_rejects_invalid_tokens() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        let health: serde_json::Value = server
+            .get("/api/health")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["data"]["status"], "OK");
+        assert!(health["data"].get("scope").is_none());
+
+        let health: serde_json::Value = server
+            .get("/api/health")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(health["data"]["scope"], "read-only");
+
+        let response = server
+            .get("/api/health")
+            .bearer_auth("not-a-session")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn data_api_requires_a_session() {
+        let server = TestServer::start(SessionManager::new()).await;
+
+        let response = server.get("/api/v1/transactions").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let response = server
+            .get("/api/v1/transactions")
+            .bearer_auth("not-a-session")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let token = server.session(SessionScope::ReadOnly);
+        let response = server
+            .get("/api/v1/transactions")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["data"], serde_json::json!([]));
+    }
+
+    #[tokio::test]
+    async fn read_only_session_cannot_write() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        let response = server
+            .request(reqwest::Method::DELETE, "/api/v1/transactions/tx-1")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let response = server
+            .get("/api/sessions")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_token_is_exchanged_once_for_a_cookie() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let bootstrap = server
+            .state
+            .session_manager
+            .generate_token(SessionScope::ReadWrite);
+        let body = serde_json::json!({ "token": bootstrap });
+
+        let response = server
+            .request(reqwest::Method::POST, "/api/session")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let cookie = response.headers()[header::SET_COOKIE]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(cookie.starts_with(&format!("{}=", SESSION_COOKIE)));
+        assert!(cookie.contains("HttpOnly"));
+        assert!(!cookie.contains("Secure"));
+
+        let session = cookie.split(';').next().unwrap();
+        let response = server
+            .get("/api/v1/transactions")
+            .header(header::COOKIE, session)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = server
+            .request(reqwest::Method::POST, "/api/session")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn pairing_requires_the_served_certificate_fingerprint() {
+        let served = "AB01".repeat(16);
+        let server = TestServer::start_with(SessionManager::new(), |state| AppState {
+            tls_fingerprint: Some(served.clone()),
+            ..state
+        })
+        .await;
+
+        let bootstrap = server
+            .state
+            .session_manager
+            .generate_token(SessionScope::ReadOnly);
+        for fingerprint in [None, Some("CD01".repeat(16))] {
+            let body = serde_json::json!({ "token": bootstrap, "fingerprint": fingerprint });
+            let response = server
+                .request(reqwest::Method::POST, "/api/session")
+                .json(&body)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::MISDIRECTED_REQUEST);
+        }
+
+        // Rejected links leave the token usable; browsers show the pin with colons
+        let body = serde_json::json!({
+            "token": bootstrap,
+            "fingerprint": lan::display_fingerprint(&served).to_lowercase(),
+        });
+        let response = server
+            .request(reqwest::Method::POST, "/api/session")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn idle_session_expires() {
+        let server = TestServer::start(SessionManager::with_expiry(
+            Duration::from_millis(50),
+            Duration::from_secs(60),
+        ))
+        .await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let response = server
+            .get("/api/v1/transactions")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn expired_sessions_do_not_count_as_guesses() {
+        let server = TestServer::start(SessionManager::with_expiry(
+            Duration::from_millis(50),
+            Duration::from_secs(60),
+        ))
+        .await;
+        let token = server.session(SessionScope::ReadOnly);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        for _ in 0..10 {
+            let response = server
+                .get("/api/v1/transactions")
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+
+        // Unknown tokens are still counted, including on the health check
+        for _ in 0..5 {
+            let response = server
+                .get("/api/health")
+                .bearer_auth("not-a-session")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = server
+            .get("/api/health")
+            .bearer_auth("not-a-session")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[tokio::test]
+    async fn sse_requires_a_session() {
+        let server = TestServer::start(SessionManager::new()).await;
+
+        let response = server.get("/api/events").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn sse_streams_events_until_shutdown() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        let mut response = server
+            .get("/api/events")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        next_event(&mut response, "connected").await;
+
+        server.state.events.publish(ServerEvent::SyncFinished {
+            success: true,
+            error: None,
+        });
+        let received = next_event(&mut response, "sync_finished").await;
+        assert!(received.contains(r#""success":true"#));
+
+        let _ = server.state.shutdown_tx.send("test".to_string());
+        next_event(&mut response, "shutdown").await;
+        let end = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap();
+        assert!(end.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn sse_ends_once_the_session_is_revoked() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        let mut response = server
+            .get("/api/events")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        next_event(&mut response, "connected").await;
+
+        let id = server.state.session_manager.list_sessions()[0].id.clone();
+        assert!(server.state.session_manager.revoke(&id));
+        server.state.events.publish(ServerEvent::SyncStarted);
+        let end = tokio::time::timeout(Duration::from_secs(5), response.chunk())
+            .await
+            .unwrap();
+        assert!(end.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn pushed_events_do_not_keep_a_session_alive() {
+        let server = TestServer::start(SessionManager::with_expiry(
+            Duration::from_millis(200),
+            Duration::from_secs(60),
+        ))
+        .await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        let mut response = server
+            .get("/api/events")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        next_event(&mut response, "connected").await;
+        for _ in 0..6 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            server.state.events.publish(ServerEvent::SyncStarted);
+        }
+
+        let response = server
+            .get("/api/v1/transactions")
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn missing_desktop_credentials_are_not_an_auth_failure() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let token = server.session(SessionScope::ReadOnly);
+
+        for _ in 0..6 {
+            let response = server
+                .get("/api/sync/api/v1/sync/pull")
+                .bearer_auth(&token)
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        }
+    }
+
+    #[tokio::test]
+    async fn ws_rpc_enforces_session_scopes() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let account = json!({ "name": "Cash", "currency": "VND" });
+
+        let mut read_only = server
+            .connect_ws(&server.session(SessionScope::ReadOnly))
+            .await;
+        let response = rpc(&mut read_only, "accounts.list", Value::Null).await;
+        assert_eq!(response["result"], json!([]));
+        for (method, params) in [
+            ("accounts.create", account.clone()),
+            ("accounts.update", json!({ "id": "a1", "data": account })),
+            ("accounts.delete", json!({ "id": "a1" })),
+            ("sync.start", Value::Null),
+        ] {
+            let response = rpc(&mut read_only, method, params).await;
+            assert_eq!(response["error"]["code"], 403, "{}", method);
+        }
+
+        let mut read_write = server
+            .connect_ws(&server.session(SessionScope::ReadWrite))
+            .await;
+        let created = rpc(&mut read_write, "accounts.create", account).await;
+        let id = created["result"]["id"].as_str().unwrap().to_string();
+        let renamed = json!({ "id": id, "data": { "name": "Wallet", "currency": "VND" } });
+        let updated = rpc(&mut read_write, "accounts.update", renamed).await;
+        assert_eq!(updated["result"]["name"], "Wallet");
+        let deleted = rpc(&mut read_write, "accounts.delete", json!({ "id": id })).await;
+        assert!(deleted.get("error").is_none());
+        assert!(server.state.data_store.get::<Account>(&id).is_none());
+    }
+
+    #[tokio::test]
+    async fn ws_rpc_rechecks_the_session_on_every_call() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let mut ws = server
+            .connect_ws(&server.session(SessionScope::ReadWrite))
+            .await;
+        let response = rpc(&mut ws, "accounts.list", Value::Null).await;
+        assert!(response.get("error").is_none());
+
+        let id = server.state.session_manager.list_sessions()[0].id.clone();
+        assert!(server.state.session_manager.revoke(&id));
+        let response = rpc(&mut ws, "accounts.list", Value::Null).await;
+        assert_eq!(response["error"]["code"], 401);
+
+        // Nothing more is pushed to a revoked session
+        server.state.events.publish(ServerEvent::SyncStarted);
+        assert!(next_message(&mut ws).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn ws_rpc_reports_malformed_calls() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let mut ws = server
+            .connect_ws(&server.session(SessionScope::ReadOnly))
+            .await;
+
+        for method in ["accounts", "accounts.rename", "ledgers.list"] {
+            let response = rpc(&mut ws, method, Value::Null).await;
+            assert_eq!(response["error"]["code"], -32601, "{}", method);
+        }
+        let response = rpc(&mut ws, "accounts.get", Value::Null).await;
+        assert_eq!(response["error"]["code"], -32602);
+        let response = rpc(&mut ws, "accounts.get", json!({ "id": "missing" })).await;
+        assert_eq!(response["error"]["code"], 404);
+
+        let response = send_rpc(&mut ws, "{not json".to_string()).await;
+        assert_eq!(
+            (response["id"].clone(), response["error"]["code"].clone()),
+            (Value::Null, json!(-32700))
+        );
+        let response = send_rpc(&mut ws, json!({ "jsonrpc": "2.0", "id": 2 }).to_string()).await;
+        assert_eq!(response["error"]["code"], -32600);
+    }
+
+    #[tokio::test]
+    async fn ws_pushes_events_resync_and_shutdown() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let mut ws = server
+            .connect_ws(&server.session(SessionScope::ReadOnly))
+            .await;
+        // Once a call is answered the socket is subscribed to events
+        rpc(&mut ws, "accounts.list", Value::Null).await;
+
+        server.state.events.publish(ServerEvent::SyncStarted);
+        let event = next_notification(&mut ws, "event").await;
+        assert_eq!(event["params"]["type"], "sync_started");
+
+        // The socket cannot read while this runs, so it falls behind
+        for _ in 0..100 {
+            server.state.events.publish(ServerEvent::SyncStarted);
+        }
+        next_notification(&mut ws, "resync").await;
+
+        server
+            .state
+            .shutdown_tx
+            .send("shutdown".to_string())
+            .unwrap();
+        next_notification(&mut ws, "shutdown").await;
+        assert!(next_message(&mut ws).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn assets_are_served_without_a_session() {
+        let server = TestServer::start(SessionManager::new()).await;
+        let index = include_str!("../tests/fixtures/dist/index.html");
+
+        let response = server.get("/").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "text/html");
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert_eq!(response.text().await.unwrap(), index);
+
+        let response = server
+            .get("/")
+            .header(header::IF_NONE_MATCH, &etag)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert!(response.text().await.unwrap().is_empty());
+
+        // Client-side routes get the app shell
+        let response = server.get("/budgets/b1").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), index);
+
+        let response = server.get("/assets/index-3f2a1b.js").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(
+            response.text().await.unwrap(),
+            include_str!("../tests/fixtures/dist/assets/index-3f2a1b.js")
+        );
+
+        let response = server.get("/assets/missing.js").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+    }
+
+    #[tokio::test]
+    async fn assets_are_sent_in_the_best_accepted_encoding() {
+        use std::io::Read;
+
+        let server = TestServer::start(SessionManager::new()).await;
+        let path = "/assets/vendor-8d41c0.js";
+        let script = include_bytes!("../tests/fixtures/dist/assets/vendor-8d41c0.js");
+        let fetch = |accept_encoding: &'static str| {
+            server
+                .get(path)
+                .header(header::ACCEPT_ENCODING, accept_encoding)
+                .send()
+        };
+
+        // Brotli comes precompressed from the build
+        let response = fetch("gzip, deflate, br").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "br");
+        assert_eq!(response.headers()[header::VARY], "Accept-Encoding");
+        let br_etag = response.headers()[header::ETAG].clone();
+        assert_eq!(
+            response.bytes().await.unwrap(),
+            &include_bytes!("../tests/fixtures/dist/assets/vendor-8d41c0.js.br")[..]
+        );
+
+        // There is no .gz fixture, so gzip is compressed at runtime
+        let response = fetch("gzip, br;q=0").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_ENCODING], "gzip");
+        assert_ne!(response.headers()[header::ETAG], br_etag);
+        let mut unzipped = Vec::new();
+        flate2::read::GzDecoder::new(&response.bytes().await.unwrap()[..])
+            .read_to_end(&mut unzipped)
+            .unwrap();
+        assert_eq!(unzipped, script);
+
+        let response = fetch("identity").await.unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+        assert_eq!(response.bytes().await.unwrap(), &script[..]);
+
+        // Too small to be worth compressing
+        let response = server
+            .get("/assets/index-3f2a1b.js")
+            .header(header::ACCEPT_ENCODING, "br, gzip")
+            .send()
+            .await
+            .unwrap();
+        assert!(response.headers().get(header::CONTENT_ENCODING).is_none());
+    }
+
+    #[tokio::test]
+    async fn foreign_host_is_rejected() {
+        let server = TestServer::start(SessionManager::new()).await;
+
+        let response = server
+            .get("/api/health")
+            .header(header::HOST, "attacker.example")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_directory_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("mi-socket-{}", crate::session::random_hex(8)));
+        std::fs::create_dir(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = dir.join(SOCKET_FILE);
+
+        let handle = start_socket_server(path.clone(), services(SessionManager::new())).unwrap();
+        let mode =
+            |path: &std::path::Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&path), 0o600);
+
+        stop_socket_server(&handle).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn socket_can_be_restarted_right_after_stopping() {
+        let dir = std::env::temp_dir().join(format!("mi-socket-{}", crate::session::random_hex(8)));
+        let path = dir.join(SOCKET_FILE);
+        let first = start_socket_server(path.clone(), services(SessionManager::new())).unwrap();
+        assert!(first.is_running());
+
+        stop_socket_server(&first).await.unwrap();
+        assert!(!first.is_running());
+        assert!(!path.exists());
+        let second = start_socket_server(path.clone(), services(SessionManager::new())).unwrap();
+        assert!(second.is_running());
+        std::os::unix::net::UnixStream::connect(&path).unwrap();
+
+        stop_socket_server(&second).await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn stop_waits_for_clients_and_releases_the_port() {
+        let services = services(SessionManager::new());
+        let session_manager = services.session_manager.clone();
+        let handle = start_web_server(services, 0).unwrap();
+        assert!(handle.is_running());
+
+        let bootstrap = session_manager.generate_token(SessionScope::ReadOnly);
+        let token = session_manager
+            .exchange_bootstrap_token(&bootstrap, None)
+            .unwrap();
+        let mut events = reqwest::Client::new()
+            .get(format!("http://{}/api/events", handle.addr))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        next_event(&mut events, "connected").await;
+
+        let addr = handle.addr;
+        stop_web_server(&handle).await.unwrap();
+        next_event(&mut events, "shutdown").await;
+        std::net::TcpListener::bind(addr).unwrap();
+    }
+}
@@ -0,0 +1,623 @@
+use chrono::{Local, NaiveDate};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::data_store::{SharedDataStore, Stored};
+use crate::events::{ChangeAction, ServerEvent, SharedEventBus};
+use crate::models::{now_iso, parse_date, Budget, Debt, DebtSettlement, Transaction};
+use crate::session::random_hex;
+
+/// Store file the webhook registry is persisted in
+pub const WEBHOOKS_STORE_FILE: &str = "webhooks.json";
+
+/// Key the registry is stored under in the webhook store
+pub const WEBHOOKS_KEY: &str = "webhooks";
+
+/// Key a webhook's signing secret is kept under in the secret store
+pub fn secret_key(webhook_id: &str) -> String {
+    format!("webhooks/{}/secret", webhook_id)
+}
+
+/// Deliveries kept in the log, newest first
+const MAX_DELIVERIES: usize = 200;
+
+/// Attempts per delivery before giving up
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry; doubles after every failed attempt
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Financial events a webhook can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEvent {
+    /// A transaction was added
+    #[serde(rename = "transaction.created")]
+    TransactionCreated,
+    /// A new transaction took a budget's current cycle over its amount
+    #[serde(rename = "budget.exceeded")]
+    BudgetExceeded,
+    /// A settlement was recorded against a debt
+    #[serde(rename = "debt.settled")]
+    DebtSettled,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TransactionCreated => "transaction.created",
+            Self::BudgetExceeded => "budget.exceeded",
+            Self::DebtSettled => "debt.settled",
+        }
+    }
+}
+
+/// A user-configured URL that receives signed event payloads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub id: String,
+    pub url: String,
+    /// HMAC-SHA256 key for the `X-Money-Insight-Signature` header. Kept in the
+    /// secret store; only registry files written before that still contain it.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub secret: String,
+    pub events: Vec<WebhookEvent>,
+    pub created_at: String,
+}
+
+impl Webhook {
+    /// Copy for the registry file
+    pub fn without_secret(&self) -> Self {
+        Self {
+            secret: String::new(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Outcome of one event sent to one webhook
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Delivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event: WebhookEvent,
+    pub success: bool,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the server answered
+    pub status: Option<u16>,
+    pub error: Option<String>,
+    pub finished_at: String,
+}
+
+/// Webhook registry plus a log of recent deliveries
+pub struct Webhooks {
+    hooks: Mutex<Vec<Webhook>>,
+    deliveries: Mutex<VecDeque<Delivery>>,
+    /// Budget id and cycle start of every `budget.exceeded` already sent
+    alerted_cycles: Mutex<HashSet<(String, NaiveDate)>>,
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<Webhook>) -> Self {
+        Self {
+            hooks: Mutex::new(hooks),
+            deliveries: Mutex::new(VecDeque::new()),
+            alerted_cycles: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Register `url` for `events` with a freshly generated signing secret
+    pub fn create(&self, url: &str, events: Vec<WebhookEvent>) -> Result<Webhook, String> {
+        let parsed = reqwest::Url::parse(url).map_err(|e| format!("Invalid webhook URL: {}", e))?;
+        if !matches!(parsed.scheme(), "http" | "https") {
+            return Err("Webhook URL must use http or https".to_string());
+        }
+        if events.is_empty() {
+            return Err("Select at least one event".to_string());
+        }
+
+        let hook = Webhook {
+            id: random_hex(8),
+            url: parsed.to_string(),
+            secret: random_hex(32),
+            events,
+            created_at: now_iso(),
+        };
+        self.hooks.lock().unwrap().push(hook.clone());
+        Ok(hook)
+    }
+
+    /// Webhooks, oldest first
+    pub fn list(&self) -> Vec<Webhook> {
+        self.hooks.lock().unwrap().clone()
+    }
+
+    /// Webhooks without their secrets, for persisting
+    pub fn records(&self) -> Vec<Webhook> {
+        self.hooks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Webhook::without_secret)
+            .collect()
+    }
+
+    /// Remove one webhook by id. Returns `false` if no such webhook exists.
+    pub fn delete(&self, webhook_id: &str) -> bool {
+        let mut hooks = self.hooks.lock().unwrap();
+        let before = hooks.len();
+        hooks.retain(|h| h.id != webhook_id);
+        hooks.len() != before
+    }
+
+    /// Recent deliveries, newest first
+    pub fn deliveries(&self) -> Vec<Delivery> {
+        self.deliveries.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn record(&self, delivery: Delivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.push_front(delivery);
+        deliveries.truncate(MAX_DELIVERIES);
+    }
+
+    fn subscribers(&self, event: WebhookEvent) -> Vec<Webhook> {
+        let hooks = self.hooks.lock().unwrap();
+        hooks
+            .iter()
+            .filter(|h| h.events.contains(&event))
+            .cloned()
+            .collect()
+    }
+
+    /// Note that `budget` was reported over for the cycle starting on `cycle_start`.
+    /// Returns `false` if it already was.
+    fn mark_alerted(&self, budget: &Budget, cycle_start: NaiveDate) -> bool {
+        self.alerted_cycles
+            .lock()
+            .unwrap()
+            .insert((budget.id.clone(), cycle_start))
+    }
+}
+
+impl Default for Webhooks {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+pub type SharedWebhooks = Arc<Webhooks>;
+
+/// Deliver webhook events for data changes published on `events` until the bus closes
+pub async fn run_dispatcher(
+    events: SharedEventBus,
+    data_store: SharedDataStore,
+    webhooks: SharedWebhooks,
+) {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+    let mut events_rx = events.subscribe();
+
+    // Budgets already over when the app starts were reported before, or went over while it was closed
+    let today = Local::now().date_naive();
+    let transactions: Vec<Transaction> = data_store.list(|_| true);
+    for budget in data_store.list(|b: &Budget| b.status == "active") {
+        if let Ok((start, _)) = budget.cycle(today) {
+            if budget.spent(&transactions, today) > budget.amount {
+                webhooks.mark_alerted(&budget, start);
+            }
+        }
+    }
+
+    loop {
+        let event = match events_rx.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                eprintln!("Webhook dispatcher missed {} events", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => break,
+        };
+
+        for (event, data) in webhook_events(&event, &data_store, &webhooks) {
+            for hook in webhooks.subscribers(event) {
+                let client = client.clone();
+                let webhooks = webhooks.clone();
+                let data = data.clone();
+                tokio::spawn(async move {
+                    let delivery = deliver(&client, &hook, event, &data, RETRY_DELAY).await;
+                    if !delivery.success {
+                        eprintln!(
+                            "Webhook {} failed after {} attempts: {}",
+                            hook.url,
+                            delivery.attempts,
+                            delivery.error.as_deref().unwrap_or("unknown error")
+                        );
+                    }
+                    webhooks.record(delivery);
+                });
+            }
+        }
+    }
+}
+
+/// Webhook events, with their payload data, caused by a server event
+fn webhook_events(
+    event: &ServerEvent,
+    data_store: &SharedDataStore,
+    webhooks: &Webhooks,
+) -> Vec<(WebhookEvent, Value)> {
+    let ServerEvent::DataChanged {
+        table,
+        action: ChangeAction::Created,
+        record: Some(record),
+        ..
+    } = event
+    else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    if *table == Transaction::TABLE {
+        found.push((
+            WebhookEvent::TransactionCreated,
+            json!({ "transaction": record }),
+        ));
+        if let Ok(tx) = serde_json::from_value::<Transaction>(record.clone()) {
+            for (budget, spent) in exceeded_budgets(&tx, data_store, webhooks) {
+                let data = json!({ "budget": budget, "spent": spent, "transaction": record });
+                found.push((WebhookEvent::BudgetExceeded, data));
+            }
+        }
+    } else if *table == DebtSettlement::TABLE {
+        let debt = record
+            .get("debtId")
+            .and_then(Value::as_str)
+            .and_then(|id| data_store.get::<Debt>(id));
+        found.push((
+            WebhookEvent::DebtSettled,
+            json!({ "settlement": record, "debt": debt }),
+        ));
+    }
+    found
+}
+
+/// Active budgets over their amount in the cycle of `tx` that were not reported
+/// for that cycle yet, with the cycle's spending
+///
+/// Spending is read when the event is handled rather than when `tx` was stored,
+/// so it may already include later transactions. Remembering reported cycles
+/// keeps that from alerting twice, or not at all.
+fn exceeded_budgets(
+    tx: &Transaction,
+    data_store: &SharedDataStore,
+    webhooks: &Webhooks,
+) -> Vec<(Budget, f64)> {
+    let Ok(date) = parse_date(&tx.date) else {
+        return Vec::new();
+    };
+    let budgets = data_store.list(|b: &Budget| b.status == "active" && b.matches(tx));
+    if budgets.is_empty() {
+        return Vec::new();
+    }
+
+    let transactions: Vec<Transaction> = data_store.list(|_| true);
+    budgets
+        .into_iter()
+        .filter_map(|budget| {
+            let (start, _) = budget.cycle(date).ok()?;
+            let spent = budget.spent(&transactions, date);
+            (spent > budget.amount && webhooks.mark_alerted(&budget, start))
+                .then_some((budget, spent))
+        })
+        .collect()
+}
+
+/// POST `data` to `hook`, retrying network errors, 429 and 5xx responses with
+/// exponential backoff starting at `retry_delay`
+async fn deliver(
+    client: &reqwest::Client,
+    hook: &Webhook,
+    event: WebhookEvent,
+    data: &Value,
+    retry_delay: Duration,
+) -> Delivery {
+    let id = uuid::Uuid::new_v4().to_string();
+    let body = json!({
+        "id": id,
+        "event": event,
+        "createdAt": now_iso(),
+        "data": data,
+    })
+    .to_string();
+
+    let mut delay = retry_delay;
+    let mut attempts = 0;
+    let (success, status, error) = loop {
+        attempts += 1;
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let result = client
+            .post(&hook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Money-Insight-Event", event.as_str())
+            .header("X-Money-Insight-Delivery", &id)
+            .header("X-Money-Insight-Timestamp", &timestamp)
+            .header(
+                "X-Money-Insight-Signature",
+                sign(&hook.secret, &timestamp, &body),
+            )
+            .body(body.clone())
+            .send()
+            .await;
+
+        let (status, error, retryable) = match result {
+            Ok(response) if response.status().is_success() => {
+                break (true, Some(response.status().as_u16()), None);
+            }
+            Ok(response) => {
+                let status = response.status();
+                let retryable =
+                    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS;
+                (
+                    Some(status.as_u16()),
+                    format!("Server answered {}", status),
+                    retryable,
+                )
+            }
+            Err(e) => (None, e.to_string(), true),
+        };
+        if !retryable || attempts >= MAX_ATTEMPTS {
+            break (false, status, Some(error));
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    };
+
+    Delivery {
+        id,
+        webhook_id: hook.id.clone(),
+        event,
+        success,
+        attempts,
+        status,
+        error,
+        finished_at: now_iso(),
+    }
+}
+
+/// `sha256=<hex>` HMAC of `<timestamp>.<body>`. Receivers recompute it with the
+/// webhook secret and should reject stale timestamps to stop replays.
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_store::DataStore;
+    use crate::models::Record;
+    use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Received requests, and how many to fail with a 500 before succeeding
+    #[derive(Clone, Default)]
+    struct StandIn {
+        requests: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+        failures: Arc<AtomicUsize>,
+    }
+
+    async fn receive(
+        State(stand_in): State<StandIn>,
+        headers: HeaderMap,
+        body: String,
+    ) -> StatusCode {
+        stand_in.requests.lock().unwrap().push((headers, body));
+        let fail = stand_in
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok();
+        if fail {
+            StatusCode::INTERNAL_SERVER_ERROR
+        } else {
+            StatusCode::NO_CONTENT
+        }
+    }
+
+    async fn start_stand_in(failures: usize) -> (String, StandIn) {
+        let stand_in = StandIn::default();
+        stand_in.failures.store(failures, Ordering::SeqCst);
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(stand_in.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, stand_in)
+    }
+
+    fn header<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+        headers.get(name).unwrap().to_str().unwrap()
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload_after_retrying_server_errors() {
+        let (url, stand_in) = start_stand_in(2).await;
+        let webhooks = Webhooks::default();
+        let hook = webhooks
+            .create(&url, vec![WebhookEvent::TransactionCreated])
+            .unwrap();
+
+        let data = json!({ "transaction": { "id": "t1" } });
+        let delivery = deliver(
+            &reqwest::Client::new(),
+            &hook,
+            WebhookEvent::TransactionCreated,
+            &data,
+            Duration::from_millis(10),
+        )
+        .await;
+
+        assert!(delivery.success);
+        assert_eq!(delivery.attempts, 3);
+        assert_eq!(delivery.status, Some(204));
+
+        let requests = stand_in.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let (headers, body) = requests.last().unwrap();
+        assert_eq!(
+            header(headers, "x-money-insight-event"),
+            "transaction.created"
+        );
+        assert_eq!(header(headers, "x-money-insight-delivery"), delivery.id);
+        let timestamp = header(headers, "x-money-insight-timestamp");
+        assert_eq!(
+            header(headers, "x-money-insight-signature"),
+            sign(&hook.secret, timestamp, body)
+        );
+
+        let payload: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(payload["event"], "transaction.created");
+        assert_eq!(payload["data"], data);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let (url, stand_in) = start_stand_in(usize::MAX).await;
+        let hook = Webhooks::default()
+            .create(&url, vec![WebhookEvent::DebtSettled])
+            .unwrap();
+
+        let delivery = deliver(
+            &reqwest::Client::new(),
+            &hook,
+            WebhookEvent::DebtSettled,
+            &Value::Null,
+            Duration::from_millis(1),
+        )
+        .await;
+
+        assert!(!delivery.success);
+        assert_eq!(delivery.attempts, MAX_ATTEMPTS);
+        assert_eq!(delivery.status, Some(500));
+        assert_eq!(
+            stand_in.requests.lock().unwrap().len(),
+            MAX_ATTEMPTS as usize
+        );
+    }
+
+    #[test]
+    fn rejects_non_http_urls() {
+        let webhooks = Webhooks::default();
+        assert!(webhooks
+            .create("file:///etc/passwd", vec![WebhookEvent::DebtSettled])
+            .is_err());
+        assert!(webhooks
+            .create("http://localhost/hook", Vec::new())
+            .is_err());
+    }
+
+    fn transaction(amount: f64) -> Transaction {
+        let input = serde_json::from_value(json!({
+            "note": "Lunch",
+            "amount": amount,
+            "category": "Food",
+            "account": "Cash",
+            "currency": "VND",
+            "date": "2026-03-10",
+        }))
+        .unwrap();
+        Transaction::create(random_hex(8), input).unwrap()
+    }
+
+    fn store_with_budget(amount: f64) -> SharedDataStore {
+        let data_store: SharedDataStore = Arc::new(DataStore::in_memory());
+        let budget_input = serde_json::from_value(json!({
+            "name": "Food",
+            "amount": amount,
+            "currency": "VND",
+            "categoryNames": ["Food"],
+            "firstCycleStartDate": "2026-01-01",
+        }))
+        .unwrap();
+        data_store
+            .insert(Budget::create("b1".into(), budget_input).unwrap())
+            .unwrap();
+        data_store
+    }
+
+    fn event_names(events: Vec<(WebhookEvent, Value)>) -> Vec<WebhookEvent> {
+        events.into_iter().map(|(event, _)| event).collect()
+    }
+
+    #[test]
+    fn reports_budget_only_when_a_transaction_crosses_it() {
+        let data_store = store_with_budget(100.0);
+        let webhooks = Webhooks::default();
+
+        let created = |tx: Transaction| {
+            let tx = data_store.insert(tx).unwrap();
+            event_names(webhook_events(
+                &ServerEvent::created(&tx),
+                &data_store,
+                &webhooks,
+            ))
+        };
+
+        assert_eq!(
+            created(transaction(-60.0)),
+            vec![WebhookEvent::TransactionCreated]
+        );
+        assert_eq!(
+            created(transaction(-50.0)),
+            vec![
+                WebhookEvent::TransactionCreated,
+                WebhookEvent::BudgetExceeded
+            ]
+        );
+        // Already over budget, so no second alert
+        assert_eq!(
+            created(transaction(-10.0)),
+            vec![WebhookEvent::TransactionCreated]
+        );
+    }
+
+    #[test]
+    fn reports_budget_once_when_events_are_handled_after_several_inserts() {
+        let data_store = store_with_budget(95.0);
+        let webhooks = Webhooks::default();
+        let alerts = |tx: &Transaction| {
+            event_names(webhook_events(
+                &ServerEvent::created(tx),
+                &data_store,
+                &webhooks,
+            ))
+            .into_iter()
+            .filter(|event| *event == WebhookEvent::BudgetExceeded)
+            .count()
+        };
+
+        let first = data_store.insert(transaction(-90.0)).unwrap();
+        assert_eq!(alerts(&first), 0);
+
+        // Both are stored before either event is handled, so each sees the other's spending
+        let second = data_store.insert(transaction(-10.0)).unwrap();
+        let third = data_store.insert(transaction(-10.0)).unwrap();
+        assert_eq!(alerts(&second) + alerts(&third), 1);
+    }
+}
@@ -0,0 +1,291 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        State,
+    },
+    http::HeaderMap,
+    response::Response,
+};
+use futures_util::{SinkExt, StreamExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::api::{self, ApiError};
+use crate::data_store::Stored;
+use crate::models::{
+    Account, Budget, Category, Debt, DebtSettlement, Transaction, TransactionFilter,
+};
+use crate::session::SessionScope;
+use crate::web_server::{authorize, session_token, still_authorized, validate_token, AppState};
+
+// JSON-RPC 2.0 error codes. Errors from the data API use their HTTP status as the code.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    /// Absent for notifications, which get no response
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Serialize)]
+struct RpcResponse {
+    jsonrpc: &'static str,
+    id: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(Serialize)]
+struct RpcNotification<'a, T: Serialize> {
+    jsonrpc: &'static str,
+    method: &'a str,
+    params: T,
+}
+
+#[derive(Debug, Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<ApiError> for RpcError {
+    fn from((status, body): ApiError) -> Self {
+        Self::new(i64::from(status.as_u16()), body.0.error.unwrap_or_default())
+    }
+}
+
+#[derive(Deserialize)]
+struct IdParams {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateParams<I> {
+    id: String,
+    data: I,
+}
+
+/// WebSocket endpoint for browser-mode RPC
+///
+/// Speaks JSON-RPC 2.0: requests are `{"jsonrpc":"2.0","id":1,"method":"accounts.list"}`,
+/// and server events are pushed on the same socket as `event` notifications.
+/// Methods are `auth.status`, `sync.start` and `<table>.list|get|create|update|delete`
+/// for each data table, with the same scopes as the REST API.
+pub async fn ws_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    validate_token(&state, &headers, SessionScope::ReadOnly)?;
+    let token = session_token(&headers).unwrap_or_default().to_string();
+    Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, token)))
+}
+
+async fn handle_socket(socket: WebSocket, state: AppState, token: String) {
+    let (mut sender, mut receiver) = socket.split();
+    let mut events_rx = state.events.subscribe();
+    let mut shutdown_rx = state.shutdown_tx.subscribe();
+
+    loop {
+        let outgoing = tokio::select! {
+            message = receiver.next() => match message {
+                Some(Ok(Message::Text(text))) => match handle_text(&state, &token, &text).await {
+                    Some(response) => to_json(&response),
+                    None => continue,
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by axum; binary frames are not part of the protocol
+                Some(Ok(_)) => continue,
+            },
+            result = events_rx.recv() => {
+                // Stop pushing data to sessions that were revoked or expired
+                if !still_authorized(&state, &token) {
+                    break;
+                }
+                match result {
+                    Ok(event) => notification("event", &event),
+                    Err(RecvError::Lagged(_)) => notification("resync", Value::Null),
+                    Err(RecvError::Closed) => break,
+                }
+            },
+            _ = shutdown_rx.recv() => {
+                let _ = sender.send(Message::Text(notification("shutdown", Value::Null))).await;
+                break;
+            },
+        };
+
+        if sender.send(Message::Text(outgoing)).await.is_err() {
+            break;
+        }
+    }
+
+    let _ = sender.close().await;
+}
+
+async fn handle_text(state: &AppState, token: &str, text: &str) -> Option<RpcResponse> {
+    let request: RpcRequest = match serde_json::from_str(text) {
+        Ok(request) => request,
+        Err(e) => {
+            let code = if serde_json::from_str::<Value>(text).is_ok() {
+                INVALID_REQUEST
+            } else {
+                PARSE_ERROR
+            };
+            return Some(response(
+                Value::Null,
+                Err(RpcError::new(code, e.to_string())),
+            ));
+        }
+    };
+
+    // Re-check the session on every call so revocation takes effect immediately
+    let result = match authorize(state, token, SessionScope::ReadOnly) {
+        Ok(scope) => call(state, scope, &request.method, request.params).await,
+        Err(e) => Err(e.into()),
+    };
+    request.id.map(|id| response(id, result))
+}
+
+async fn call(
+    state: &AppState,
+    scope: SessionScope,
+    method: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    let (namespace, action) = method
+        .split_once('.')
+        .ok_or_else(|| RpcError::new(METHOD_NOT_FOUND, format!("Unknown method {}", method)))?;
+
+    match (namespace, action) {
+        ("auth", "status") => state
+            .bridge
+            .auth_status()
+            .await
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e)),
+        ("sync", "start") => {
+            require(scope, SessionScope::ReadWrite)?;
+            state
+                .bridge
+                .request_sync()
+                .map_err(|e| RpcError::new(INTERNAL_ERROR, e))?;
+            Ok(Value::Null)
+        }
+        ("transactions", "list") => {
+            let filter: TransactionFilter = parse_params(params)?;
+            to_value(api::transactions(state, &filter))
+        }
+        ("transactions", _) => table_call::<Transaction>(state, scope, action, params),
+        ("accounts", _) => table_call::<Account>(state, scope, action, params),
+        ("categories", _) => table_call::<Category>(state, scope, action, params),
+        ("budgets", _) => table_call::<Budget>(state, scope, action, params),
+        ("debts", _) => table_call::<Debt>(state, scope, action, params),
+        ("debt_settlements", _) => table_call::<DebtSettlement>(state, scope, action, params),
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )),
+    }
+}
+
+fn table_call<T: Stored>(
+    state: &AppState,
+    scope: SessionScope,
+    action: &str,
+    params: Value,
+) -> Result<Value, RpcError> {
+    match action {
+        "list" => to_value(state.data_store.list(|_: &T| true)),
+        "get" => {
+            let IdParams { id } = parse_params(params)?;
+            to_value(api::find::<T>(state, &id)?)
+        }
+        "create" => {
+            require(scope, SessionScope::ReadWrite)?;
+            to_value(api::create::<T>(state, parse_params(params)?)?)
+        }
+        "update" => {
+            require(scope, SessionScope::ReadWrite)?;
+            let UpdateParams { id, data } = parse_params::<UpdateParams<T::Input>>(params)?;
+            to_value(api::update::<T>(state, &id, data)?)
+        }
+        "delete" => {
+            require(scope, SessionScope::ReadWrite)?;
+            let IdParams { id } = parse_params(params)?;
+            api::delete::<T>(state, &id)?;
+            Ok(Value::Null)
+        }
+        _ => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("Unknown method {}.{}", T::TABLE, action),
+        )),
+    }
+}
+
+fn require(scope: SessionScope, required: SessionScope) -> Result<(), RpcError> {
+    if scope < required {
+        return Err(RpcError::new(
+            403,
+            format!("A {} session cannot perform this action", scope.as_str()),
+        ));
+    }
+    Ok(())
+}
+
+// Omitted params are treated as an empty object
+fn parse_params<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() {
+        Value::Object(Default::default())
+    } else {
+        params
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_value(value: impl Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> RpcResponse {
+    let (result, error) = match result {
+        Ok(value) => (Some(value), None),
+        Err(error) => (None, Some(error)),
+    };
+    RpcResponse {
+        jsonrpc: "2.0",
+        id,
+        result,
+        error,
+    }
+}
+
+fn notification(method: &str, params: impl Serialize) -> String {
+    to_json(&RpcNotification {
+        jsonrpc: "2.0",
+        method,
+        params,
+    })
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
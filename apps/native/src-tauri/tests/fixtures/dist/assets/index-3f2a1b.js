@@ -0,0 +1 @@
+document.getElementById("root").textContent = "Money Insight";
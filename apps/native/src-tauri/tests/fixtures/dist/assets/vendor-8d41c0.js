@@ -0,0 +1,51 @@
+const CURRENCIES = {
+  VND: { symbol: "₫", fractionDigits: 0, locale: "vi-VN" },
+  USD: { symbol: "$", fractionDigits: 2, locale: "en-US" },
+  EUR: { symbol: "€", fractionDigits: 2, locale: "de-DE" },
+  JPY: { symbol: "¥", fractionDigits: 0, locale: "ja-JP" },
+};
+
+export function formatAmount(amount, currency = "VND") {
+  const config = CURRENCIES[currency] ?? CURRENCIES.VND;
+  return new Intl.NumberFormat(config.locale, {
+    style: "currency",
+    currency,
+    minimumFractionDigits: config.fractionDigits,
+    maximumFractionDigits: config.fractionDigits,
+  }).format(amount);
+}
+
+export function formatDate(date, locale = "vi-VN") {
+  return new Intl.DateTimeFormat(locale, {
+    year: "numeric",
+    month: "2-digit",
+    day: "2-digit",
+  }).format(new Date(date));
+}
+
+export function groupByMonth(transactions) {
+  const groups = new Map();
+  for (const transaction of transactions) {
+    const month = transaction.date.slice(0, 7);
+    const group = groups.get(month) ?? [];
+    group.push(transaction);
+    groups.set(month, group);
+  }
+  return [...groups.entries()].sort(([a], [b]) => b.localeCompare(a));
+}
+
+export function sumByCategory(transactions) {
+  const totals = new Map();
+  for (const transaction of transactions) {
+    const key = transaction.categoryId ?? "uncategorized";
+    totals.set(key, (totals.get(key) ?? 0) + transaction.amount);
+  }
+  return totals;
+}
+
+export function budgetProgress(budget, transactions) {
+  const spent = transactions
+    .filter((t) => t.categoryId === budget.categoryId && t.amount < 0)
+    .reduce((total, t) => total - t.amount, 0);
+  return { spent, remaining: budget.amount - spent, ratio: spent / budget.amount };
+}
@@ -1,4 +1,4 @@
-import { getSessionToken, WEB_APP_PORT } from "@money-insight/ui/utils";
+import { getWebAppUrl } from "@money-insight/ui/utils";
 
 /**
  * API response wrapper
@@ -10,26 +10,17 @@ interface ApiResponse<T> {
 }
 
 /**
- * Base class for HTTP-based adapters that communicate with the desktop SQLite backend.
+ * Base class for HTTP-based adapters that communicate with the desktop backend.
+ * Requests are authenticated by the HttpOnly session cookie.
  */
 export class HttpAdapter {
-  protected baseUrl = `http://localhost:${WEB_APP_PORT}/api`;
-
-  protected getToken(): string | null {
-    return getSessionToken();
-  }
+  protected baseUrl = `${getWebAppUrl()}/api`;
 
   protected async get<T>(
     endpoint: string,
     params?: Record<string, unknown>,
   ): Promise<T> {
-    const token = this.getToken();
-    if (!token) {
-      throw new Error("No session token. Please open from desktop app.");
-    }
-
     const url = new URL(`${this.baseUrl}${endpoint}`);
-    url.searchParams.append("token", token);
 
     if (params) {
       Object.entries(params).forEach(([key, value]) => {
@@ -43,7 +34,7 @@ export class HttpAdapter {
       });
     }
 
-    const response = await fetch(url.toString());
+    const response = await fetch(url.toString(), { credentials: "include" });
     if (!response.ok) {
       throw new Error(`HTTP ${response.status}: ${response.statusText}`);
     }
@@ -57,16 +48,11 @@ export class HttpAdapter {
   }
 
   protected async post<T>(endpoint: string, body: unknown): Promise<T> {
-    const token = this.getToken();
-    if (!token) {
-      throw new Error("No session token. Please open from desktop app.");
-    }
-
     const url = new URL(`${this.baseUrl}${endpoint}`);
-    url.searchParams.append("token", token);
 
     const response = await fetch(url.toString(), {
       method: "POST",
+      credentials: "include",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify(body),
     });
@@ -84,16 +70,11 @@ export class HttpAdapter {
   }
 
   protected async put<T>(endpoint: string, body: unknown): Promise<T> {
-    const token = this.getToken();
-    if (!token) {
-      throw new Error("No session token. Please open from desktop app.");
-    }
-
     const url = new URL(`${this.baseUrl}${endpoint}`);
-    url.searchParams.append("token", token);
 
     const response = await fetch(url.toString(), {
       method: "PUT",
+      credentials: "include",
       headers: { "Content-Type": "application/json" },
       body: JSON.stringify(body),
     });
@@ -111,16 +92,11 @@ export class HttpAdapter {
   }
 
   protected async delete<T>(endpoint: string): Promise<T> {
-    const token = this.getToken();
-    if (!token) {
-      throw new Error("No session token. Please open from desktop app.");
-    }
-
     const url = new URL(`${this.baseUrl}${endpoint}`);
-    url.searchParams.append("token", token);
 
     const response = await fetch(url.toString(), {
       method: "DELETE",
+      credentials: "include",
     });
 
     if (!response.ok) {
@@ -0,0 +1,123 @@
+import type { Table, Transaction as DexieTransaction } from "dexie";
+import { invoke } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
+import { trackDelete, type MoneyInsightDatabase } from "@money-insight/ui/adapters/web";
+
+/** Tables the embedded web server serves to browser mode */
+const MIRRORED_TABLES = [
+  "transactions",
+  "accounts",
+  "categories",
+  "budgets",
+  "debts",
+  "debtSettlements",
+] as const;
+
+/** Tauri event carrying a row written through the web API */
+const DATA_WRITTEN_EVENT = "browser-data-written";
+
+/** Batch changes made in quick succession, e.g. a CSV import, into one call */
+const FLUSH_DELAY_MS = 50;
+
+type Row = Record<string, unknown> & { id: string; syncVersion?: number };
+
+interface DesktopChange {
+  table: string;
+  id: string;
+  /** The row after the change; absent for deletions */
+  row?: Row | null;
+}
+
+/**
+ * Keep the web server's copy of the data in step with this window's IndexedDB,
+ * and apply rows written through the web API here so they sync like local edits.
+ * Returns a function that stops mirroring.
+ */
+export function startTauriDataMirror(db: MoneyInsightDatabase): () => void {
+  let pending: DesktopChange[] = [];
+  let timer: ReturnType<typeof setTimeout> | null = null;
+
+  // Changes are sent after the snapshot, so it never overwrites them
+  let sending = Promise.all(MIRRORED_TABLES.map((name) => db.table(name).toArray()))
+    .then((rows) => {
+      const snapshot = Object.fromEntries(MIRRORED_TABLES.map((name, i) => [name, rows[i]]));
+      return invoke<void>("browser_data_snapshot", { snapshot });
+    })
+    .catch((e) => console.error("browser_data_snapshot failed:", e));
+
+  const send = (command: string, args: Record<string, unknown>) => {
+    sending = sending
+      .then(() => invoke<void>(command, args))
+      .catch((e) => console.error(`${command} failed:`, e));
+  };
+
+  const flush = () => {
+    timer = null;
+    const changes = pending;
+    pending = [];
+    if (changes.length > 0) send("browser_data_changed", { changes });
+  };
+
+  const queue = (trans: DexieTransaction, change: DesktopChange) => {
+    // Only committed changes are sent; a rolled back transaction sends nothing
+    trans.on("complete", () => {
+      pending.push(change);
+      timer ??= setTimeout(flush, FLUSH_DELAY_MS);
+    });
+  };
+
+  const unhooks = MIRRORED_TABLES.map((name) => {
+    const table = db.table(name) as Table<Row, string>;
+    const creating = function (_key: string, obj: Row, trans: DexieTransaction) {
+      queue(trans, { table: name, id: obj.id, row: { ...obj } });
+    };
+    const updating = function (
+      this: { onsuccess?: (updated: Row) => void },
+      _mods: object,
+      key: string,
+      _obj: Row,
+      trans: DexieTransaction,
+    ) {
+      this.onsuccess = (updated) => queue(trans, { table: name, id: key, row: { ...updated } });
+    };
+    const deleting = function (key: string, _obj: Row, trans: DexieTransaction) {
+      queue(trans, { table: name, id: key, row: null });
+    };
+    table.hook("creating", creating);
+    table.hook("updating", updating);
+    table.hook("deleting", deleting);
+    return () => {
+      table.hook("creating").unsubscribe(creating);
+      table.hook("updating").unsubscribe(updating);
+      table.hook("deleting").unsubscribe(deleting);
+    };
+  });
+
+  const unlisten = listen<DesktopChange>(DATA_WRITTEN_EVENT, (event) => {
+    void applyWrite(db, event.payload).catch((e) =>
+      console.error("Failed to apply browser write:", e),
+    );
+  });
+
+  return () => {
+    unhooks.forEach((unhook) => unhook());
+    void unlisten.then((stop) => stop());
+    if (timer) clearTimeout(timer);
+    flush();
+  };
+}
+
+/** Store a row written through the web API, keeping fields the server does not know about */
+async function applyWrite(db: MoneyInsightDatabase, change: DesktopChange): Promise<void> {
+  if (!(MIRRORED_TABLES as readonly string[]).includes(change.table)) return;
+  const table = db.table(change.table) as Table<Row, string>;
+  await db.transaction("rw", [table, db._pendingChanges], async () => {
+    const existing = await table.get(change.id);
+    if (change.row) {
+      await table.put({ ...existing, ...change.row });
+    } else if (existing) {
+      await trackDelete(change.table, change.id, existing.syncVersion || 0);
+      await table.delete(change.id);
+    }
+  });
+}
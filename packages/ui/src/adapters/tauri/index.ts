@@ -1 +1,2 @@
 export { TauriAuthAdapter } from "./TauriAuthAdapter";
+export { startTauriDataMirror } from "./TauriDataMirror";
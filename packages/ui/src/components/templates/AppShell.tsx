@@ -11,7 +11,7 @@ import { useAuth, useNav } from "@money-insight/ui/hooks";
 import { useSpendingStore } from "@money-insight/ui/stores";
 import {
   isOpenedFromDesktop,
-  initializeSessionToken,
+  initializeBrowserSession,
 } from "@money-insight/ui/utils";
 
 const SIDEBAR_COLLAPSED_KEY = "money-insight-sidebar-collapsed";
@@ -97,13 +97,13 @@ export function AppShell({
 
   const skipAuth = skipAuthProp || localSkipAuth;
 
-  // CRITICAL: Initialize session token FIRST, then database
+  // CRITICAL: Initialize browser session FIRST, then database
   useEffect(() => {
     const initialize = async () => {
       if (isOpenedFromDesktop()) {
-        const token = initializeSessionToken();
-        if (!token) {
-          console.error("[AppShell] Failed to initialize session token");
+        const hasSession = await initializeBrowserSession();
+        if (!hasSession) {
+          console.error("[AppShell] Failed to initialize browser session");
         }
       }
       try {
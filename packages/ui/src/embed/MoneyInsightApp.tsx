@@ -20,12 +20,16 @@ import {
 } from "@money-insight/ui/adapters";
 import {
   initDb,
+  getDb,
   deleteCurrentDb,
   IndexedDBSyncStorage,
   ensureCategoryBackfill,
 } from "@money-insight/ui/adapters/web";
 import { QmServerAuthAdapter } from "@money-insight/ui/adapters/shared";
-import { TauriAuthAdapter } from "@money-insight/ui/adapters/tauri";
+import {
+  TauriAuthAdapter,
+  startTauriDataMirror,
+} from "@money-insight/ui/adapters/tauri";
 import { isTauri } from "@money-insight/ui/utils";
 import { useEffect, useMemo, useRef, useState } from "react";
 import { BrowserRouter } from "react-router-dom";
@@ -105,6 +109,12 @@ export function MoneyInsightApp({
       .catch(console.error);
   }, [authTokens?.userId]);
 
+  // Browser mode serves this window's data through the embedded web server
+  useEffect(() => {
+    if (!dbReady || !isTauri()) return;
+    return startTauriDataMirror(getDb());
+  }, [dbReady]);
+
   // Register logout cleanup with hub after DB is ready
   useEffect(() => {
     if (!dbReady || !registerLogoutCleanup) return;
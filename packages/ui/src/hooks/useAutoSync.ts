@@ -1,7 +1,12 @@
-import { useCallback, useEffect, useRef } from "react";
-import type { ISyncService } from "@money-insight/ui/adapters/factory/interfaces";
-import type { SyncResult } from "@money-insight/shared";
-import { refreshLocalStoresAfterSync } from "@money-insight/ui/services/syncService";
+import { useCallback, useEffect, useRef } from "react";
+import type { ISyncService } from "@money-insight/ui/adapters/factory/interfaces";
+import type { SyncResult } from "@money-insight/shared";
+import {
+  refreshLocalStoresAfterSync,
+  withSyncNotifications,
+} from "@money-insight/ui/services/syncService";
+import { isTauri } from "@money-insight/ui/utils";
+import { listen } from "@tauri-apps/api/event";
 
 interface UseAutoSyncOptions {
   syncService: ISyncService | null;
@@ -23,13 +28,13 @@ export function useAutoSync({
   const doSync = useCallback(async () => {
     if (!syncService || isSyncingRef.current) return;
     isSyncingRef.current = true;
-    onSyncStart?.();
-    try {
-      const result = await syncService.syncNow();
-      await refreshLocalStoresAfterSync(result);
-      onSyncResult?.(result);
-    } catch (e) {
-      console.warn("[auto-sync] failed:", e);
+    onSyncStart?.();
+    try {
+      const result = await withSyncNotifications(() => syncService.syncNow());
+      await refreshLocalStoresAfterSync(result);
+      onSyncResult?.(result);
+    } catch (e) {
+      console.warn("[auto-sync] failed:", e);
       onSyncResult?.({
         pushed: 0,
         pulled: 0,
@@ -83,4 +88,17 @@ export function useAutoSync({
       document.removeEventListener("visibilitychange", handleVisibility);
     };
   }, [enabled, syncService, intervalMs, doSync]);
+
+  // Browser tabs opened from the desktop app ask this window to sync
+  useEffect(() => {
+    if (!enabled || !syncService || !isTauri()) return;
+
+    const unlisten = listen("sync-requested", () => {
+      void doSync();
+    });
+
+    return () => {
+      void unlisten.then((stop) => stop());
+    };
+  }, [enabled, syncService, doSync]);
 }
@@ -1,9 +1,6 @@
 import { useState, useEffect } from "react";
-import {
-  isOpenedFromDesktop,
-  getSessionToken,
-  WEB_APP_PORT,
-} from "@money-insight/ui/utils";
+import { isOpenedFromDesktop, getWebAppUrl } from "@money-insight/ui/utils";
+import { reloadLocalStores } from "@money-insight/ui/services/syncService";
 
 interface ServerConnectionState {
   isConnected: boolean;
@@ -13,7 +10,8 @@ interface ServerConnectionState {
 }
 
 /**
- * Hook to maintain SSE connection to desktop server for shutdown detection.
+ * Hook to maintain SSE connection to desktop server for shutdown detection
+ * and to reload data when it changes in another window or tab.
  * Only active when running in browser mode opened from desktop.
  */
 export function useServerConnection() {
@@ -30,12 +28,6 @@ export function useServerConnection() {
       return;
     }
 
-    const token = getSessionToken();
-    if (!token) {
-      setState((prev) => ({ ...prev, error: "No session token" }));
-      return;
-    }
-
     let eventSource: EventSource | null = null;
     let reconnectTimeout: ReturnType<typeof setTimeout> | null = null;
     let isMounted = true;
@@ -43,10 +35,11 @@ export function useServerConnection() {
     const connect = () => {
       if (!isMounted) return;
 
-      const url = `http://localhost:${WEB_APP_PORT}/api/events?token=${encodeURIComponent(token)}`;
+      // Session cookie authenticates the stream
+      const url = `${getWebAppUrl()}/api/events`;
 
       try {
-        eventSource = new EventSource(url);
+        eventSource = new EventSource(url, { withCredentials: true });
 
         eventSource.onopen = () => {
           if (!isMounted) return;
@@ -68,6 +61,14 @@ export function useServerConnection() {
           // Keepalive - no action needed
         });
 
+        // Data changed elsewhere, a sync pulled new data, or events were missed
+        for (const name of ["data_changed", "sync_finished", "resync"]) {
+          eventSource.addEventListener(name, () => {
+            if (!isMounted) return;
+            void reloadLocalStores();
+          });
+        }
+
         eventSource.addEventListener("shutdown", () => {
           if (!isMounted) return;
           console.log("[SSE] Server shutdown notification received");
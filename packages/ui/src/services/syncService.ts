@@ -1,4 +1,6 @@
 import { getSyncService } from "@money-insight/ui/adapters";
+import { isTauri } from "@money-insight/ui/utils";
+import { invoke } from "@tauri-apps/api/core";
 import type {
   SyncProgress,
   SyncResult,
@@ -15,7 +17,14 @@ export async function refreshLocalStoresAfterSync(
 ): Promise<void> {
   if (!result.success) return;
   if (!options?.force && !shouldRefreshLocalStoresAfterSync(result)) return;
+  await reloadLocalStores();
+}
 
+/**
+ * Reload the in-memory stores from the database, e.g. after another window
+ * or browser tab changed data.
+ */
+export async function reloadLocalStores(): Promise<void> {
   try {
     const { useSpendingStore, useDebtStore } = await import(
       "@money-insight/ui/stores"
@@ -38,8 +47,42 @@ export async function refreshLocalStoresAfterSync(
   }
 }
 
+/**
+ * Report sync progress to the desktop backend, which forwards it to browser
+ * tabs opened from the desktop app.
+ */
+export async function withSyncNotifications(
+  run: () => Promise<SyncResult>,
+): Promise<SyncResult> {
+  if (!isTauri()) return run();
+
+  const notify = (command: string, args?: Record<string, unknown>) =>
+    invoke<void>(command, args).catch((error) =>
+      console.error(`[sync] Failed to report ${command}:`, error),
+    );
+
+  // The local data is shared by all profiles; after a switch it only syncs
+  // once the user confirms it belongs to the new one
+  await invoke<void>("auth_ensure_sync_allowed");
+  await notify("notify_sync_started");
+  try {
+    const result = await run();
+    await notify("notify_sync_finished", {
+      success: result.success,
+      error: result.error ?? null,
+    });
+    return result;
+  } catch (error) {
+    await notify("notify_sync_finished", {
+      success: false,
+      error: String(error),
+    });
+    throw error;
+  }
+}
+
 export async function syncNow(): Promise<SyncResult> {
-  const result = await getSyncService().syncNow();
+  const result = await withSyncNotifications(() => getSyncService().syncNow());
   await refreshLocalStoresAfterSync(result, { force: true });
   return result;
 }
@@ -52,9 +95,9 @@ export async function syncWithProgress(
   onProgress: (progress: SyncProgress) => void,
 ): Promise<SyncResult> {
   const svc = getSyncService();
-  const result = svc.syncWithProgress
-    ? await svc.syncWithProgress(onProgress)
-    : await svc.syncNow();
+  const result = await withSyncNotifications(() =>
+    svc.syncWithProgress ? svc.syncWithProgress(onProgress) : svc.syncNow(),
+  );
   await refreshLocalStoresAfterSync(result, { force: true });
   return result;
 }
@@ -7,6 +7,11 @@
  */
 export const WEB_APP_PORT = 25096;
 
+/**
+ * Port of the Vite dev server, which talks to the embedded server cross-origin
+ */
+const VITE_DEV_PORT = 1420;
+
 /**
  * Check if running inside Tauri webview
  */
@@ -52,7 +57,13 @@ export const hasNativeNotifications = (): boolean => isTauri();
 
 // Session storage keys
 const DESKTOP_MODE_KEY = "browser_sync_desktop_mode";
-const SESSION_TOKEN_KEY = "browser_sync_session_token";
+
+// URL parameter carrying the one-time bootstrap token from the desktop app
+const BOOTSTRAP_TOKEN_PARAM = "session";
+
+// URL parameter telling a Vite dev page which port the embedded server bound
+const API_PORT_PARAM = "apiPort";
+const API_PORT_KEY = "browser_sync_api_port";
 
 /**
  * Check if browser was opened from desktop app
@@ -65,31 +76,22 @@ export const isOpenedFromDesktop = (): boolean => {
   if (storedMode === "true") return true;
   if (storedMode === "false") return false;
 
-  // Validate origin
-  const origin = window.location.origin;
-  const validOrigins = [
-    `http://localhost:${WEB_APP_PORT}`, // Production embedded server
-    "http://localhost:1420", // Vite dev server
-  ];
-
-  if (!validOrigins.includes(origin)) {
+  // Validate origin: the embedded server (on its default or fallback port)
+  // and the Vite dev server are always served from localhost, while LAN
+  // pairing links are HTTPS and pin the server's certificate
+  const params = new URLSearchParams(window.location.search);
+  const isLocal =
+    window.location.protocol === "http:" &&
+    window.location.hostname === "localhost";
+  const isLanPairing =
+    window.location.protocol === "https:" && params.has(FINGERPRINT_PARAM);
+  if (!isLocal && !isLanPairing) {
     sessionStorage.setItem(DESKTOP_MODE_KEY, "false");
     return false;
   }
 
-  // Check for session token in URL
-  const params = new URLSearchParams(window.location.search);
-  const sessionToken = params.get("session");
-
-  if (sessionToken) {
-    sessionStorage.setItem(DESKTOP_MODE_KEY, "true");
-    sessionStorage.setItem(SESSION_TOKEN_KEY, sessionToken);
-    return true;
-  }
-
-  // Check stored token from previous navigation
-  const storedToken = sessionStorage.getItem(SESSION_TOKEN_KEY);
-  if (storedToken) {
+  // Bootstrap token present means we were just opened by the desktop app
+  if (params.get(BOOTSTRAP_TOKEN_PARAM)) {
     sessionStorage.setItem(DESKTOP_MODE_KEY, "true");
     return true;
   }
@@ -99,67 +101,50 @@ export const isOpenedFromDesktop = (): boolean => {
 };
 
 /**
- * Get session token for API authentication
- */
-export const getSessionToken = (): string | null => {
-  const params = new URLSearchParams(window.location.search);
-  const urlToken = params.get("session");
-  if (urlToken) {
-    sessionStorage.setItem(SESSION_TOKEN_KEY, urlToken);
-    return urlToken;
-  }
-  return sessionStorage.getItem(SESSION_TOKEN_KEY);
-};
-
-/**
- * Initialize session token from URL or sessionStorage
- * This MUST be called before any API operations in browser mode
- * Provides detailed logging for debugging
+ * Exchange the one-time bootstrap token from the URL for an HttpOnly
+ * session cookie, then strip the token from the address bar.
+ * This MUST be called before any API operations in browser mode.
  *
- * @returns The session token if found, null otherwise
+ * @returns true if a browser session is established
  */
-export const initializeSessionToken = (): string | null => {
-  // If running in Tauri, no session token needed
+export const initializeBrowserSession = async (): Promise<boolean> => {
+  // If running in Tauri, no browser session needed
   if (isTauri()) {
-    console.log("[Session] Running in Tauri mode - no session token needed");
-    return null;
+    console.log("[Session] Running in Tauri mode - no session needed");
+    return false;
   }
 
-  console.log("[Session] Initializing session token...");
-  console.log("[Session] Current URL:", window.location.href);
-  console.log("[Session] Search params:", window.location.search);
+  const url = new URL(window.location.href);
+  const bootstrapToken = url.searchParams.get(BOOTSTRAP_TOKEN_PARAM);
 
-  // First, try to get token from URL
-  const params = new URLSearchParams(window.location.search);
-  const urlToken = params.get("session");
-
-  if (urlToken) {
-    console.log(
-      "[Session] Found token in URL:",
-      urlToken.substring(0, 8) + "...",
-    );
-    sessionStorage.setItem(SESSION_TOKEN_KEY, urlToken);
-    sessionStorage.setItem(DESKTOP_MODE_KEY, "true");
-    return urlToken;
+  if (!bootstrapToken) {
+    // Cookie from a previous exchange in this tab
+    return sessionStorage.getItem(DESKTOP_MODE_KEY) === "true";
   }
 
-  // If not in URL, check sessionStorage
-  const storedToken = sessionStorage.getItem(SESSION_TOKEN_KEY);
-  if (storedToken) {
-    console.log(
-      "[Session] Found token in sessionStorage:",
-      storedToken.substring(0, 8) + "...",
-    );
+  // Never leave the token in history, even if the exchange fails
+  url.searchParams.delete(BOOTSTRAP_TOKEN_PARAM);
+  window.history.replaceState(window.history.state, "", url.toString());
+
+  try {
+    const response = await fetch(`${getWebAppUrl()}/api/session`, {
+      method: "POST",
+      credentials: "include",
+      headers: { "Content-Type": "application/json" },
+      body: JSON.stringify({ token: bootstrapToken }),
+    });
+    if (!response.ok) {
+      throw new Error(`HTTP ${response.status}: ${response.statusText}`);
+    }
     sessionStorage.setItem(DESKTOP_MODE_KEY, "true");
-    return storedToken;
+    console.log("[Session] Browser session established");
+    return true;
+  } catch (error) {
+    console.error("[Session] Failed to exchange bootstrap token:", error);
+    console.error("[Session] Please open the app from the desktop application.");
+    sessionStorage.setItem(DESKTOP_MODE_KEY, "false");
+    return false;
   }
-
-  // No token found
-  console.error("[Session] No session token found!");
-  console.error("[Session] Browser mode requires session token.");
-  console.error("[Session] Please open the app from the desktop application.");
-  sessionStorage.setItem(DESKTOP_MODE_KEY, "false");
-  return null;
 };
 
 /**
@@ -167,12 +152,37 @@ export const initializeSessionToken = (): string | null => {
  */
 export const clearDesktopModeSession = (): void => {
   sessionStorage.removeItem(DESKTOP_MODE_KEY);
-  sessionStorage.removeItem(SESSION_TOKEN_KEY);
+  sessionStorage.removeItem(API_PORT_KEY);
 };
 
 /**
  * Get the URL for the embedded web server
  */
 export const getWebAppUrl = (): string => {
-  return `http://localhost:${WEB_APP_PORT}`;
+  // Pages served by the embedded server are same-origin with its API, even
+  // when it fell back to a port other than WEB_APP_PORT; only the LAN server
+  // serves HTTPS
+  if (
+    typeof window !== "undefined" &&
+    !isTauri() &&
+    ((window.location.hostname === "localhost" &&
+      window.location.port !== String(VITE_DEV_PORT)) ||
+      window.location.protocol === "https:")
+  ) {
+    return window.location.origin;
+  }
+  return `http://localhost:${getDevApiPort()}`;
+};
+
+/**
+ * Port of the embedded server as passed by the desktop app, kept for the
+ * rest of the tab's life since the page may be reloaded without it
+ */
+const getDevApiPort = (): string => {
+  if (typeof window === "undefined") return String(WEB_APP_PORT);
+  const fromUrl = new URLSearchParams(window.location.search).get(API_PORT_PARAM);
+  if (fromUrl && /^\d+$/.test(fromUrl)) {
+    sessionStorage.setItem(API_PORT_KEY, fromUrl);
+  }
+  return sessionStorage.getItem(API_PORT_KEY) ?? String(WEB_APP_PORT);
 };